@@ -0,0 +1,75 @@
+use super::stats::QueueCounters;
+use std::io::{stderr, Result, Write};
+use std::sync::mpsc::{channel, sync_channel, Sender, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+
+enum Message {
+    Data(Vec<u8>),
+    Flush(Sender<()>),
+}
+
+/// A stream handing its output to a background thread through a bounded queue.\
+/// Output is dropped and counted if the queue is full, so logging never blocks on a slow stream.
+/// Flushing waits until the background thread has written and flushed all queued output.
+pub(crate) struct AsyncWriter {
+    sender: SyncSender<Message>,
+    counters: Arc<QueueCounters>,
+}
+
+impl AsyncWriter {
+    pub fn new(
+        mut stream: Box<dyn Write + Send>,
+        capacity: usize,
+        counters: Arc<QueueCounters>,
+    ) -> AsyncWriter {
+        let (sender, receiver) = sync_channel(capacity);
+        let thread_counters = counters.clone();
+        thread::spawn(move || {
+            // exits once the writer is dropped and the queue is drained
+            for message in receiver {
+                match message {
+                    Message::Data(data) => {
+                        let _res = stream.write_all(&data);
+                        thread_counters.dequeued();
+                    }
+                    Message::Flush(done) => {
+                        let _res = stream.flush();
+                        let _res = done.send(());
+                    }
+                }
+            }
+            let _res = stream.flush();
+        });
+        AsyncWriter { sender, counters }
+    }
+}
+
+impl Write for AsyncWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        // count before sending, the background thread might dequeue right away
+        let depth = self.counters.enqueued();
+        match self.sender.try_send(Message::Data(buf.to_vec())) {
+            Ok(_) => {
+                self.counters.queued(depth);
+                Ok(buf.len())
+            }
+            Err(TrySendError::Full(_)) => {
+                self.counters.dropped();
+                Ok(buf.len())
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.counters.dequeued();
+                stderr().write(buf)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let (done, wait) = channel();
+        if self.sender.send(Message::Flush(done)).is_ok() {
+            let _res = wait.recv();
+        }
+        Ok(())
+    }
+}
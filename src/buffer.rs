@@ -0,0 +1,123 @@
+use std::io::{Result, Write};
+
+/// The memory buffer of buffer destinations.\
+/// The buffer either grows as needed or uses fixed storage as a ring, overwriting the oldest
+/// output once it is full.
+pub(crate) enum LogBuffer {
+    Growing(Vec<u8>),
+    Ring {
+        storage: Box<[u8]>,
+        start: usize,
+        len: usize,
+    },
+}
+
+/// Reverse the order of the lines in contents, newest line first.\
+/// Every line is terminated by a newline, including a last line that had none, so it does not
+/// run into the line following it.
+pub(crate) fn reverse_lines(contents: &[u8]) -> Vec<u8> {
+    let mut reversed = Vec::with_capacity(contents.len() + 1);
+    for line in contents.split_inclusive(|byte| *byte == b'\n').rev() {
+        reversed.extend_from_slice(line);
+        if !line.ends_with(b"\n") {
+            reversed.push(b'\n');
+        }
+    }
+    reversed
+}
+
+impl LogBuffer {
+    pub fn new() -> LogBuffer {
+        LogBuffer::Growing(Vec::new())
+    }
+
+    /// A ring buffer using storage, storage must not be empty
+    pub fn fixed(storage: Box<[u8]>) -> LogBuffer {
+        LogBuffer::Ring {
+            storage,
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// The buffer contents, oldest output first
+    pub fn contents(&self) -> Vec<u8> {
+        match self {
+            LogBuffer::Growing(buffer) => buffer.clone(),
+            LogBuffer::Ring {
+                storage,
+                start,
+                len,
+            } => {
+                let end = start + len;
+                if end <= storage.len() {
+                    storage[*start..end].to_vec()
+                } else {
+                    let mut contents = storage[*start..].to_vec();
+                    contents.extend_from_slice(&storage[..end - storage.len()]);
+                    contents
+                }
+            }
+        }
+    }
+
+    /// Change the capacity of a ring buffer keeping the most recent output, capacity must not be
+    /// 0. Returns false for a growing buffer.
+    pub fn resize(&mut self, capacity: usize) -> bool {
+        match self {
+            LogBuffer::Growing(_) => false,
+            LogBuffer::Ring { .. } => {
+                let contents = self.contents();
+                let kept = &contents[contents.len().saturating_sub(capacity)..];
+                let mut storage = vec![0u8; capacity].into_boxed_slice();
+                storage[..kept.len()].copy_from_slice(kept);
+                *self = LogBuffer::Ring {
+                    storage,
+                    start: 0,
+                    len: kept.len(),
+                };
+                true
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        match self {
+            LogBuffer::Growing(buffer) => buffer.clear(),
+            LogBuffer::Ring { start, len, .. } => {
+                *start = 0;
+                *len = 0;
+            }
+        }
+    }
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            LogBuffer::Growing(buffer) => buffer.extend_from_slice(buf),
+            LogBuffer::Ring {
+                storage,
+                start,
+                len,
+            } => {
+                let capacity = storage.len();
+                // only the last capacity bytes can be kept
+                let data = &buf[buf.len().saturating_sub(capacity)..];
+                let pos = (*start + *len) % capacity;
+                let first = data.len().min(capacity - pos);
+                storage[pos..pos + first].copy_from_slice(&data[..first]);
+                storage[..data.len() - first].copy_from_slice(&data[first..]);
+
+                let overflow = (*len + data.len()).saturating_sub(capacity);
+                *start = (*start + overflow) % capacity;
+                *len = (*len + data.len()).min(capacity);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use std::io::{stderr, Result, Write};
+use std::sync::mpsc::Sender;
+
+/// A stream sending each written line to a channel.\
+/// Once the receiver is disconnected all further output goes to stderr.
+pub(crate) struct ChannelWriter {
+    sender: Option<Sender<String>>,
+}
+
+impl ChannelWriter {
+    pub fn new(sender: Sender<String>) -> ChannelWriter {
+        ChannelWriter {
+            sender: Some(sender),
+        }
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if let Some(ref sender) = self.sender {
+            let line = String::from_utf8_lossy(buf).into_owned();
+            match sender.send(line) {
+                Ok(_) => return Ok(buf.len()),
+                Err(why) => {
+                    // receiver is gone, don't try again
+                    self.sender = None;
+                    return stderr().write(why.0.as_bytes());
+                }
+            }
+        }
+        stderr().write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.sender.is_some() {
+            Ok(())
+        } else {
+            stderr().flush()
+        }
+    }
+}
@@ -1,20 +1,135 @@
 #![cfg(feature = "config")]
-use log::Level;
+use log::{Level, LevelFilter};
+use serde::de::IgnoredAny;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::error;
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
+use std::result;
 use std::str::FromStr;
 
 use serde_yaml;
 
 use crate::{
     error::{Error, ErrorKind, Result, ToError},
-    LogDestination, DEFAULT_LOG_DEST, DEFAULT_LOG_LEVEL,
+    level_config::{parse_level, ALL_MODULES},
+    LogDestination,
 };
 
 // TODO: create log config builder and initialise Logger with config object, instead of using complex parameters for Logger::initialise
 
+/// The supported config file formats
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ConfigFormat {
+    Yaml,
+    /// requires feature config_toml
+    Toml,
+    /// requires feature config_json
+    Json,
+}
+
+const FORMAT_TX: &[(&str, ConfigFormat); 4] = &[
+    ("yaml", ConfigFormat::Yaml),
+    ("yml", ConfigFormat::Yaml),
+    ("toml", ConfigFormat::Toml),
+    ("json", ConfigFormat::Json),
+];
+
+impl ConfigFormat {
+    /// Determine the format from the file extension, defaults to YAML
+    pub fn from_path(path: &Path) -> ConfigFormat {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| ConfigFormat::from_str(ext).ok())
+            .unwrap_or(ConfigFormat::Yaml)
+    }
+
+    fn parse(&self, config_str: &str, config_path: &Path) -> Result<LogConfigFile> {
+        match self {
+            ConfigFormat::Yaml => {
+                let deserializer = serde_yaml::Deserializer::from_str(config_str);
+                serde_path_to_error::deserialize(deserializer).map_err(|why| {
+                    let location = why.inner().location().map(|loc| (loc.line(), loc.column()));
+                    self.parse_error(config_path, location, why)
+                })
+            }
+            #[cfg(feature = "config_toml")]
+            ConfigFormat::Toml => {
+                let deserializer = toml::Deserializer::new(config_str);
+                serde_path_to_error::deserialize(deserializer).map_err(|why| {
+                    let location = why
+                        .inner()
+                        .span()
+                        .map(|span| line_column(config_str, span.start));
+                    self.parse_error(config_path, location, why)
+                })
+            }
+            #[cfg(feature = "config_json")]
+            ConfigFormat::Json => {
+                let mut deserializer = serde_json::Deserializer::from_str(config_str);
+                serde_path_to_error::deserialize(&mut deserializer).map_err(|why| {
+                    let location = Some((why.inner().line(), why.inner().column()));
+                    self.parse_error(config_path, location, why)
+                })
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::with_context(
+                ErrorKind::InvParam,
+                &format!("Config format {:?} is not enabled in this build", self),
+            )),
+        }
+    }
+
+    /// Create an error describing where parsing the config file failed
+    fn parse_error<E: error::Error + Send + Sync + 'static>(
+        &self,
+        config_path: &Path,
+        location: Option<(usize, usize)>,
+        why: serde_path_to_error::Error<E>,
+    ) -> Error {
+        let mut context = format!(
+            "failed to deserialize {:?} config from '{}'",
+            self,
+            config_path.display()
+        );
+        if let Some((line, column)) = location {
+            context.push_str(&format!(" at line {}, column {}", line, column));
+        }
+        let key = why.path().to_string();
+        if key != "." {
+            context.push_str(&format!(", key '{}'", key));
+        }
+        Error::with_all(ErrorKind::InvParam, &context, Box::new(why.into_inner()))
+    }
+}
+
+/// Convert a byte offset in text to a line and column number, both starting at 1
+#[cfg(feature = "config_toml")]
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map(|pos| pos + 1).unwrap_or(0) + 1;
+    (line, column)
+}
+
+impl FromStr for ConfigFormat {
+    type Err = Error;
+    fn from_str(format: &str) -> result::Result<Self, Self::Err> {
+        if let Some(pos) = FORMAT_TX
+            .iter()
+            .position(|val| val.0.eq_ignore_ascii_case(format))
+        {
+            Ok(FORMAT_TX[pos].1)
+        } else {
+            Err(Error::with_context(
+                ErrorKind::InvParam,
+                &format!("Invalid config format string encountered: '{}'", format),
+            ))
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct LogConfigFile {
     default_level: Option<String>,
@@ -25,21 +140,25 @@ struct LogConfigFile {
     color: Option<bool>,
     brief_info: Option<bool>,
     // TODO: allow to configure buffer max, implement ring buffer for log
+    // keys not matching any setting, rejected when parsing strictly
+    #[serde(flatten)]
+    unknown: HashMap<String, IgnoredAny>,
 }
 
+// settings are None if they have not been set, so merging can leave them alone
 pub struct LogConfig {
-    default_level: Level,
+    default_level: Option<LevelFilter>,
     mod_level: HashMap<String, Level>,
-    log_dest: LogDestination,
+    log_dest: Option<LogDestination>,
     log_stream: Option<PathBuf>,
-    color: bool,
-    brief_info: bool,
+    color: Option<bool>,
+    brief_info: Option<bool>,
 }
 
 /// The logger configuration parameters
 /// Used in Logger::set_log_config
 impl<'a> LogConfig {
-    pub(crate) fn get_default_level(&'a self) -> Level {
+    pub(crate) fn get_default_level(&'a self) -> Option<LevelFilter> {
         self.default_level
     }
 
@@ -47,19 +166,19 @@ impl<'a> LogConfig {
         &self.mod_level
     }
 
-    pub(crate) fn get_log_dest(&'a self) -> &'a LogDestination {
-        &self.log_dest
+    pub(crate) fn get_log_dest(&'a self) -> Option<&'a LogDestination> {
+        self.log_dest.as_ref()
     }
 
     pub(crate) fn get_log_stream(&'a self) -> &'a Option<PathBuf> {
         &self.log_stream
     }
 
-    pub(crate) fn is_color(&self) -> bool {
+    pub(crate) fn is_color(&self) -> Option<bool> {
         self.color
     }
 
-    pub(crate) fn is_brief_info(&self) -> bool {
+    pub(crate) fn is_brief_info(&self) -> Option<bool> {
         self.brief_info
     }
 }
@@ -70,78 +189,166 @@ pub struct LogConfigBuilder {
 
 /// LogConfigBuilder helps creating a configuration for logger.
 impl<'a> LogConfigBuilder {
-    /// Create a new LogConfigBuilder with defaults for all config settings.
+    /// Create a new LogConfigBuilder with no settings set.\
+    /// Settings that are not set take their defaults, unless the configuration is merged.
     pub fn new() -> LogConfigBuilder {
         LogConfigBuilder {
             inner: LogConfig {
-                default_level: DEFAULT_LOG_LEVEL,
+                default_level: None,
                 mod_level: HashMap::new(),
-                log_dest: DEFAULT_LOG_DEST,
+                log_dest: None,
                 log_stream: None,
-                color: false,
-                brief_info: false,
+                color: None,
+                brief_info: None,
             },
         }
     }
 
-    /// Create LogConfigBuilder with initial values taken from a YAML config file and defaults
+    /// Create LogConfigBuilder with initial values taken from a config file and defaults.\
+    /// The format of the file is derived from its extension, see ConfigFormat::from_path.
     pub fn from_file<P: AsRef<Path>>(filename: P) -> Result<LogConfigBuilder> {
-        let config_path = filename.as_ref();
+        let format = ConfigFormat::from_path(filename.as_ref());
+        LogConfigBuilder::from_file_with_format(filename, format)
+    }
 
+    /// Create LogConfigBuilder with initial values taken from a config file in the given format
+    /// and defaults
+    pub fn from_file_with_format<P: AsRef<Path>>(
+        filename: P,
+        format: ConfigFormat,
+    ) -> Result<LogConfigBuilder> {
+        LogConfigBuilder::parse_file(filename.as_ref(), format, false)
+    }
+
+    /// Create LogConfigBuilder from a config file like LogConfigBuilder::from_file, but fail
+    /// with ```ErrorKind::InvParam``` naming the offending key if the file contains keys that
+    /// are not settings, eg. a misspelled ```breif_info```.
+    pub fn from_file_strict<P: AsRef<Path>>(filename: P) -> Result<LogConfigBuilder> {
+        let format = ConfigFormat::from_path(filename.as_ref());
+        LogConfigBuilder::parse_file(filename.as_ref(), format, true)
+    }
+
+    /// Create LogConfigBuilder from a config file in the given format, rejecting unknown keys
+    /// like LogConfigBuilder::from_file_strict
+    pub fn from_file_with_format_strict<P: AsRef<Path>>(
+        filename: P,
+        format: ConfigFormat,
+    ) -> Result<LogConfigBuilder> {
+        LogConfigBuilder::parse_file(filename.as_ref(), format, true)
+    }
+
+    fn parse_file(
+        config_path: &Path,
+        format: ConfigFormat,
+        strict: bool,
+    ) -> Result<LogConfigBuilder> {
         let config_str = &read_to_string(config_path).upstream_with_context(&format!(
             "config::from_file: failed to read {}",
             config_path.display()
         ))?;
 
-        let cfg_file: LogConfigFile = serde_yaml::from_str(config_str)
-            .upstream_with_context("failed to deserialze config from yaml")?;
+        let cfg_file = format.parse(config_str, config_path)?;
+
+        if strict {
+            let mut unknown: Vec<&String> = cfg_file.unknown.keys().collect();
+            unknown.sort();
+            if let Some(key) = unknown.first() {
+                return Err(Error::with_context(
+                    ErrorKind::InvParam,
+                    &format!(
+                        "unknown key '{}' in config file '{}'",
+                        key,
+                        config_path.display()
+                    ),
+                ));
+            }
+        }
 
         let mut builder = LogConfigBuilder::new();
 
         if let Some(ref level_str) = cfg_file.default_level {
-            builder.inner.default_level = Level::from_str(level_str)
-                .upstream_with_context(&format!("Invalid log level: '{}'", level_str))?;
+            builder.inner.default_level = Some(parse_level(level_str).error_with_all(
+                ErrorKind::InvParam,
+                &format!("default_level: invalid log level: '{}'", level_str),
+            )?);
         }
 
         if let Some(ref mod_level) = cfg_file.mod_level {
             for (mod_name, mod_level) in mod_level {
+                // the entry for all modules overrides default_level
+                if mod_name == ALL_MODULES {
+                    builder.inner.default_level = Some(parse_level(mod_level).error_with_all(
+                        ErrorKind::InvParam,
+                        &format!("mod_level.{}: invalid log level: '{}'", mod_name, mod_level),
+                    )?);
+                    continue;
+                }
                 builder.inner.mod_level.insert(
                     mod_name.clone(),
-                    Level::from_str(mod_level).error_with_all(
-                        ErrorKind::InvParam,
-                        &format!("Invalid log level: '{}'", mod_level),
-                    )?,
+                    // module levels can not be switched off
+                    parse_level(mod_level)
+                        .ok()
+                        .and_then(|level| level.to_level())
+                        .ok_or_else(|| {
+                            Error::with_context(
+                                ErrorKind::InvParam,
+                                &format!(
+                                    "mod_level.{}: invalid log level: '{}'",
+                                    mod_name, mod_level
+                                ),
+                            )
+                        })?,
                 );
             }
         }
 
         if let Some(ref dest_str) = cfg_file.log_dest {
-            let dest = LogDestination::from_str(dest_str)?;
+            let dest = LogDestination::from_str(dest_str).error_with_all(
+                ErrorKind::InvParam,
+                &format!("log_dest: invalid log destination: '{}'", dest_str),
+            )?;
             if dest.is_stream_dest() {
                 if let Some(stream) = cfg_file.log_stream {
-                    builder.inner.log_dest = dest;
+                    builder.inner.log_dest = Some(dest);
                     builder.inner.log_stream = Some(stream)
                 } else {
                     return Err(Error::with_context(
                         ErrorKind::InvParam,
                         &format!(
-                            "Missing log stream parameter for log destination {:?}",
-                            dest
+                            "log_stream: missing, it is required for log_dest '{}'",
+                            dest_str
                         ),
                     ));
                 }
+            } else if let Some(ref stream) = cfg_file.log_stream {
+                return Err(Error::with_context(
+                    ErrorKind::InvParam,
+                    &format!(
+                        "log_stream: '{}' is only valid with a stream log_dest, log_dest is '{}'",
+                        stream.display(),
+                        dest_str
+                    ),
+                ));
             } else {
-                builder.inner.log_dest = dest;
+                builder.inner.log_dest = Some(dest);
             }
             // TODO: read params for future ring buffer size
+        } else if let Some(ref stream) = cfg_file.log_stream {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                &format!(
+                    "log_stream: '{}' is only valid with a stream log_dest, log_dest is not set",
+                    stream.display()
+                ),
+            ));
         }
 
         if let Some(color) = cfg_file.color {
-            builder.inner.color = color;
+            builder.inner.color = Some(color);
         }
 
         if let Some(brief_info) = cfg_file.brief_info {
-            builder.inner.brief_info = brief_info;
+            builder.inner.brief_info = Some(brief_info);
         }
 
         Ok(builder)
@@ -149,14 +356,18 @@ impl<'a> LogConfigBuilder {
 
     /// Set the default log Level
     pub fn set_default_level(&'a mut self, level: Level) -> &'a mut LogConfigBuilder {
-        self.inner.default_level = level;
+        self.inner.default_level = Some(level.to_level_filter());
         self
     }
 
     /// Set the log level for a module
-    /// Format of module is <module>[::<submodule>[::<submodule>]]
+    /// Format of module is <module>[::<submodule>[::<submodule>]], ```*``` sets the default level
     pub fn set_mod_level(&'a mut self, module: &str, level: Level) -> &'a mut LogConfigBuilder {
-        let _dummy = self.inner.mod_level.insert(String::from(module), level);
+        if module == ALL_MODULES {
+            self.inner.default_level = Some(level.to_level_filter());
+        } else {
+            let _dummy = self.inner.mod_level.insert(String::from(module), level);
+        }
         self
     }
 
@@ -177,7 +388,7 @@ impl<'a> LogConfigBuilder {
                 ));
             }
         }
-        self.inner.log_dest = dest;
+        self.inner.log_dest = Some(dest);
 
         Ok(self)
     }
@@ -185,12 +396,12 @@ impl<'a> LogConfigBuilder {
     /// Enable / disable brief info format.
     /// Brief info displays info messages without the source module
     pub fn set_brief_info(&'a mut self, val: bool) {
-        self.inner.brief_info = val;
+        self.inner.brief_info = Some(val);
     }
 
     /// Enable / disable colored output
     pub fn set_color(&'a mut self, val: bool) {
-        self.inner.color = val;
+        self.inner.color = Some(val);
     }
 
     /// Build the configuration
@@ -0,0 +1,56 @@
+use std::cell::RefCell;
+
+thread_local! {
+    // the context labels of the current thread, see Logger::push_context
+    static CONTEXT: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Push label, returns the new number of labels
+pub(crate) fn push(label: &str) -> usize {
+    CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        context.push(String::from(label));
+        context.len()
+    })
+}
+
+pub(crate) fn pop() -> Option<String> {
+    CONTEXT.with(|context| context.borrow_mut().pop())
+}
+
+/// The context of the current thread as breadcrumb, eg. ```[request:42 > db]```, None if empty
+pub(crate) fn breadcrumb() -> Option<String> {
+    CONTEXT.with(|context| {
+        let context = context.borrow();
+        if context.is_empty() {
+            None
+        } else {
+            Some(format!("[{}]", context.join(" > ")))
+        }
+    })
+}
+
+/// Removes a context label from the current thread when dropped, see Logger::enter_context
+#[must_use = "the context is removed when the guard is dropped"]
+pub struct ContextGuard {
+    // the number of labels including the guarded one
+    depth: usize,
+    // the guard must be dropped on the thread that pushed the label
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl ContextGuard {
+    pub(crate) fn new(label: &str) -> ContextGuard {
+        ContextGuard {
+            depth: push(label),
+            _not_send: std::marker::PhantomData,
+        }
+    }
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        // also removes labels pushed later and not popped
+        CONTEXT.with(|context| context.borrow_mut().truncate(self.depth - 1));
+    }
+}
@@ -95,11 +95,8 @@ impl Error {
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.kind)?;
-        match &self.context {
-            Some(context) => {
-                write!(f, ", context: {}", context)?;
-            }
-            None => (),
+        if let Some(context) = &self.context {
+            write!(f, ", context: {}", context)?;
         }
         let mut curr_err: &dyn error::Error = self;
 
@@ -133,22 +130,14 @@ impl error::Error for Error {
 }
 
 pub trait ToError<T> {
-    fn error(self) -> Result<T>;
     fn upstream_with_context(self, context: &str) -> Result<T>;
     fn error_with_all(self, kind: ErrorKind, context: &str) -> Result<T>;
-    fn error_with_kind(self, kind: ErrorKind) -> Result<T>;
 }
 
 impl<T, E> ToError<T> for result::Result<T, E>
 where
     E: error::Error + Send + Sync + 'static,
 {
-    fn error(self) -> Result<T> {
-        match self {
-            Ok(ok) => Ok(ok),
-            Err(why) => Err(Error::with_cause(ErrorKind::Upstream, Box::new(why))),
-        }
-    }
     fn error_with_all(self, kind: ErrorKind, context: &str) -> Result<T> {
         match self {
             Ok(ok) => Ok(ok),
@@ -156,12 +145,6 @@ where
         }
     }
 
-    fn error_with_kind(self, kind: ErrorKind) -> Result<T> {
-        match self {
-            Ok(ok) => Ok(ok),
-            Err(why) => Err(Error::with_cause(kind, Box::new(why))),
-        }
-    }
     fn upstream_with_context(self, context: &str) -> Result<T> {
         match self {
             Ok(ok) => Ok(ok),
@@ -0,0 +1,791 @@
+use super::pattern::{LogFields, SharedFormatFn};
+use super::theme::ansi_color;
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use colored::Color;
+use log::{Level, Record};
+use std::borrow::Cow;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// marks continuation lines of multi-line messages
+const MULTILINE_MARKER: &str = "| ";
+// appended to truncated messages
+pub(crate) const TRUNCATED_MARKER: &str = "…[truncated]";
+
+pub(crate) const CSV_HEADER: &str = "timestamp,level,module,message\n";
+
+/// The output formats of the logger
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum LogFormat {
+    /// human readable text lines
+    Text,
+    /// comma separated values: timestamp,level,module,message
+    Csv,
+    /// one JSON object per line, see FieldNames for the keys
+    Json,
+    /// MessagePack maps of timestamp, level, module and message, each prefixed by its length
+    /// as 32 bit big endian integer, requires feature msgpack. Binary output is only written to
+    /// files, streams and the buffer, it can't be used with log destinations writing to the
+    /// console.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl LogFormat {
+    /// True for binary formats, which can't be written to the console or mixed with text lines
+    pub(crate) fn is_binary(self) -> bool {
+        #[cfg(feature = "msgpack")]
+        if self == LogFormat::MessagePack {
+            return true;
+        }
+        false
+    }
+}
+
+/// The fields of text output, see Logger::set_field_order
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Field {
+    /// the timestamp, if enabled for the sink
+    Timestamp,
+    /// the level label, eg. ```INFO```
+    Level,
+    /// the module name in brackets along with target and source location if enabled
+    Module,
+    /// the message
+    Message,
+}
+
+/// Consistent sets of display settings, see Logger::set_preset
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputPreset {
+    /// colored text with milliseconds, source locations and full module paths
+    Development,
+    /// JSON without color, timestamps in UTC in RFC 3339 format
+    Production,
+    /// text with level and message only
+    Minimal,
+}
+
+/// The styles of timestamps, see Logger::set_timestamp_style
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimestampStyle {
+    /// local date and time, eg. ```2020-01-01 12:00:00```
+    DateTime,
+    /// local time without the date, eg. ```12:00:00```
+    TimeOnly,
+    /// RFC 3339 date and time, eg. ```2020-01-01T12:00:00Z```
+    Rfc3339,
+    /// seconds since the logger was created, eg. ```12s```
+    Uptime,
+}
+
+/// Format the timestamp of a record logged at now, uptime after the logger was created
+pub(crate) fn format_timestamp(
+    style: TimestampStyle,
+    now: DateTime<FixedOffset>,
+    uptime: Duration,
+    millis: bool,
+) -> String {
+    let pattern = match style {
+        TimestampStyle::Rfc3339 => {
+            let seconds = if millis {
+                SecondsFormat::Millis
+            } else {
+                SecondsFormat::Secs
+            };
+            return format!("{} ", now.to_rfc3339_opts(seconds, true));
+        }
+        TimestampStyle::Uptime if millis => {
+            return format!("{}.{:03}s ", uptime.as_secs(), uptime.subsec_millis());
+        }
+        TimestampStyle::Uptime => return format!("{}s ", uptime.as_secs()),
+        TimestampStyle::DateTime => "%Y-%m-%d %H:%M:%S",
+        TimestampStyle::TimeOnly => "%H:%M:%S",
+    };
+    if millis {
+        let ts_millis = now.timestamp_millis() % 1000;
+        format!("{}.{:03} ", now.format(pattern), ts_millis)
+    } else {
+        format!("{} ", now.format(pattern))
+    }
+}
+
+/// How much of the module path text output shows, see Logger::set_module_truncate
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ModuleTrunc {
+    /// the full module path, eg. ```app::db::pool```
+    Full,
+    /// the first segment of the module path, eg. ```app```
+    TopLevel,
+    /// the last segment of the module path, eg. ```pool```
+    Leaf,
+}
+
+impl ModuleTrunc {
+    /// The part of module shown
+    pub(crate) fn apply<'a>(&self, module: &'a str) -> &'a str {
+        match self {
+            ModuleTrunc::Full => module,
+            ModuleTrunc::TopLevel => module.split("::").next().unwrap_or(module),
+            ModuleTrunc::Leaf => module.rsplit("::").next().unwrap_or(module),
+        }
+    }
+}
+
+/// Abbreviate the middle segments of module if it is longer than max_len characters, eg.
+/// ```app::…::query``` for ```app::db::pool::query```.\
+/// The first and the last segment are always kept, followed by as many of the segments before
+/// the last one as fit. A max_len of 0 keeps module as is.
+pub(crate) fn abbreviate_module(module: &str, max_len: usize) -> Cow<'_, str> {
+    if max_len == 0 || module.chars().count() <= max_len {
+        return Cow::Borrowed(module);
+    }
+    let segments: Vec<&str> = module.split("::").collect();
+    if segments.len() < 3 {
+        return Cow::Borrowed(module);
+    }
+
+    let head = format!("{}::…", segments[0]);
+    let mut len = head.chars().count() + 2 + segments[segments.len() - 1].chars().count();
+    let mut first_kept = segments.len() - 1;
+    while first_kept > 2 {
+        let segment_len = 2 + segments[first_kept - 1].chars().count();
+        if len + segment_len > max_len {
+            break;
+        }
+        len += segment_len;
+        first_kept -= 1;
+    }
+    Cow::Owned(format!("{}::{}", head, segments[first_kept..].join("::")))
+}
+
+/// The alignment of a column, see ColumnLayout
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Align {
+    /// padded at the end, truncated at the end
+    Left,
+    /// padded at the start, truncated at the start
+    Right,
+}
+
+/// Fixed column widths of text output, see Logger::set_column_layout.\
+/// Fields shorter than their column are padded, longer ones are truncated with an ellipsis
+/// according to the alignment of the column. Fields without a column keep their natural width.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ColumnLayout {
+    timestamp: Option<(usize, Align)>,
+    level: Option<(usize, Align)>,
+    module: Option<(usize, Align)>,
+    message: Option<(usize, Align)>,
+}
+
+impl<'a> ColumnLayout {
+    /// A layout without columns
+    pub fn new() -> ColumnLayout {
+        ColumnLayout::default()
+    }
+
+    /// Set the column of the timestamp
+    pub fn set_timestamp(&'a mut self, width: usize, align: Align) -> &'a mut ColumnLayout {
+        self.timestamp = Some((width, align));
+        self
+    }
+
+    /// Set the column of the level label, including the sequence number if enabled
+    pub fn set_level(&'a mut self, width: usize, align: Align) -> &'a mut ColumnLayout {
+        self.level = Some((width, align));
+        self
+    }
+
+    /// Set the column of the module tag, including target and source location if enabled
+    pub fn set_module(&'a mut self, width: usize, align: Align) -> &'a mut ColumnLayout {
+        self.module = Some((width, align));
+        self
+    }
+
+    /// Set the column of the message, applies to every line of multi-line messages
+    pub fn set_message(&'a mut self, width: usize, align: Align) -> &'a mut ColumnLayout {
+        self.message = Some((width, align));
+        self
+    }
+
+    /// The column of field
+    pub(crate) fn column(&self, field: Field) -> Option<(usize, Align)> {
+        match field {
+            Field::Timestamp => self.timestamp,
+            Field::Level => self.level,
+            Field::Module => self.module,
+            Field::Message => self.message,
+        }
+    }
+}
+
+/// Pad or truncate text to width characters. Truncated text is marked with an ellipsis, at the
+/// end for Align::Left and at the start for Align::Right. Left aligned text is only padded if pad
+/// is set, so no trailing blanks are written at the end of a line.
+pub(crate) fn fit_column(text: &str, width: usize, align: Align, pad: bool) -> Cow<'_, str> {
+    let len = text.chars().count();
+    if len > width {
+        let keep = width.saturating_sub(1);
+        Cow::Owned(match align {
+            Align::Left => format!("{}…", text.chars().take(keep).collect::<String>()),
+            Align::Right => format!("…{}", text.chars().skip(len - keep).collect::<String>()),
+        })
+    } else if len == width || (align == Align::Left && !pad) {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(match align {
+            Align::Left => format!("{:<width$}", text, width = width),
+            Align::Right => format!("{:>width$}", text, width = width),
+        })
+    }
+}
+
+/// The default field order of text output
+pub(crate) const DEFAULT_FIELD_ORDER: [Field; 4] = [
+    Field::Timestamp,
+    Field::Level,
+    Field::Module,
+    Field::Message,
+];
+
+/// The keys used in JSON output, see Logger::set_json_field_names.\
+/// The optional fields thread, pid, file and line are only included if a key is set for them,
+/// the sequence number is included if enabled by Logger::set_show_seq.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldNames {
+    timestamp: String,
+    level: String,
+    module: String,
+    message: String,
+    thread: Option<String>,
+    pid: Option<String>,
+    file: Option<String>,
+    line: Option<String>,
+    seq: String,
+}
+
+impl<'a> FieldNames {
+    /// The default keys timestamp, level, module and message without optional fields
+    pub fn new() -> FieldNames {
+        FieldNames {
+            timestamp: String::from("timestamp"),
+            level: String::from("level"),
+            module: String::from("module"),
+            message: String::from("message"),
+            thread: None,
+            pid: None,
+            file: None,
+            line: None,
+            seq: String::from("seq"),
+        }
+    }
+
+    /// Set the key of the timestamp
+    pub fn set_timestamp(&'a mut self, name: &str) -> &'a mut FieldNames {
+        self.timestamp = String::from(name);
+        self
+    }
+
+    /// Set the key of the level
+    pub fn set_level(&'a mut self, name: &str) -> &'a mut FieldNames {
+        self.level = String::from(name);
+        self
+    }
+
+    /// Set the key of the module
+    pub fn set_module(&'a mut self, name: &str) -> &'a mut FieldNames {
+        self.module = String::from(name);
+        self
+    }
+
+    /// Set the key of the message
+    pub fn set_message(&'a mut self, name: &str) -> &'a mut FieldNames {
+        self.message = String::from(name);
+        self
+    }
+
+    /// Include the thread name under the given key, ```None``` excludes it
+    pub fn set_thread(&'a mut self, name: Option<&str>) -> &'a mut FieldNames {
+        self.thread = name.map(String::from);
+        self
+    }
+
+    /// Include the process id under the given key, ```None``` excludes it
+    pub fn set_pid(&'a mut self, name: Option<&str>) -> &'a mut FieldNames {
+        self.pid = name.map(String::from);
+        self
+    }
+
+    /// Include the source file under the given key, ```None``` excludes it
+    pub fn set_file(&'a mut self, name: Option<&str>) -> &'a mut FieldNames {
+        self.file = name.map(String::from);
+        self
+    }
+
+    /// Include the source line under the given key, ```None``` excludes it
+    pub fn set_line(&'a mut self, name: Option<&str>) -> &'a mut FieldNames {
+        self.line = name.map(String::from);
+        self
+    }
+
+    /// Set the key of the sequence number
+    pub fn set_seq(&'a mut self, name: &str) -> &'a mut FieldNames {
+        self.seq = String::from(name);
+        self
+    }
+
+    /// The keys that may be output, in output order. The key of the sequence number is included
+    /// even though it is only written while records are numbered.
+    pub(crate) fn keys(&'a self) -> Vec<&'a str> {
+        let mut keys = vec![
+            self.timestamp.as_str(),
+            self.level.as_str(),
+            self.module.as_str(),
+            self.message.as_str(),
+        ];
+        for key in [&self.thread, &self.pid, &self.file, &self.line]
+            .into_iter()
+            .flatten()
+        {
+            keys.push(key.as_str());
+        }
+        keys.push(self.seq.as_str());
+        keys
+    }
+}
+
+impl Default for FieldNames {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The resolved parts of a record passed to a Formatter
+pub struct LogMeta<'a> {
+    /// the module name as shown in the output
+    pub module: &'a str,
+    /// the module tag used to look up module levels
+    pub module_tag: &'a str,
+    /// the timestamp, empty if no sink is timestamped
+    pub timestamp: &'a str,
+    /// the level label, eg. ```INFO``` or ```AUDIT```
+    pub label: &'a str,
+    /// true if the record is an audit record
+    pub audit: bool,
+    /// true if brief info is enabled for the record
+    pub brief: bool,
+}
+
+/// A function formatting records, see Logger::set_formatter
+pub type Formatter = Box<dyn Fn(&Record, &LogMeta) -> String + Send>;
+
+/// The parts of a log line shared by all sinks, rendered per sink by Line::render
+pub(crate) struct Line {
+    pub level: Level,
+    pub label: String,
+    /// the color of the whole line, if colored by level or module only
+    pub color: Option<Color>,
+    /// the colors of the level and the module tag, if colored by level and module
+    pub tag_colors: Option<(Color, Color)>,
+    /// the module with brackets as shown in the module head
+    pub module_label: String,
+    pub timestamp: String,
+    /// when the record was logged, sent to the network destinations
+    #[cfg(any(feature = "gelf", feature = "otlp"))]
+    pub time: std::time::SystemTime,
+    pub module: String,
+    pub level_head: String,
+    pub module_head: String,
+    pub field_order: Arc<[Field]>,
+    pub message: String,
+    pub format_fn: Option<SharedFormatFn>,
+    pub formatted: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub seq: Option<u64>,
+    pub json_fields: Arc<FieldNames>,
+    pub columns: Option<Arc<ColumnLayout>>,
+}
+
+/// Sink specific rendering options
+pub(crate) struct RenderOpts {
+    pub format: LogFormat,
+    pub timestamp: bool,
+    pub wrap_width: Option<usize>,
+    pub multiline: bool,
+    pub color: bool,
+}
+
+/// The rendering options of all sinks
+pub(crate) struct SinkOpts {
+    pub console: RenderOpts,
+    pub stream: RenderOpts,
+    pub buffer: RenderOpts,
+}
+
+/// The level head of text output, the label preceded by the sequence number if enabled
+pub(crate) fn level_head(seq: Option<u64>, label: &str) -> String {
+    match seq {
+        Some(seq) => format!("#{:06} {:<5} ", seq, label),
+        None => format!("{:<5} ", label),
+    }
+}
+
+impl Line {
+    /// Number the line, eg. once a line kept for replay is written
+    pub fn set_seq(&mut self, seq: Option<u64>) {
+        self.seq = seq;
+        self.level_head = level_head(seq, &self.label);
+    }
+
+    /// The output written to sinks, binary for LogFormat::MessagePack.\
+    /// Pre-formatted lines are encoded as MessagePack records with the formatted text as message.
+    pub fn render_bytes(&self, opts: &RenderOpts) -> Vec<u8> {
+        #[cfg(feature = "msgpack")]
+        if opts.format == LogFormat::MessagePack {
+            return self.render_msgpack(opts);
+        }
+        self.render(opts).into_bytes()
+    }
+
+    #[cfg(feature = "msgpack")]
+    fn render_msgpack(&self, opts: &RenderOpts) -> Vec<u8> {
+        #[derive(serde::Serialize)]
+        struct Record<'a> {
+            timestamp: &'a str,
+            level: &'a str,
+            module: &'a str,
+            message: &'a str,
+        }
+
+        let record = Record {
+            timestamp: if opts.timestamp {
+                self.timestamp.trim_end()
+            } else {
+                ""
+            },
+            level: &self.label,
+            module: &self.module,
+            message: self.formatted.as_deref().unwrap_or(&self.message),
+        };
+        // serializing a map of strings can't fail
+        let payload = rmp_serde::to_vec_named(&record).unwrap_or_default();
+        let mut output = Vec::with_capacity(payload.len() + 4);
+        output.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        output.extend_from_slice(&payload);
+        output
+    }
+
+    pub fn render(&self, opts: &RenderOpts) -> String {
+        if let Some(ref formatted) = self.formatted {
+            let output = format!("{}\n", formatted);
+            return if opts.color && opts.format == LogFormat::Text {
+                self.paint(output, "")
+            } else {
+                output
+            };
+        }
+
+        match opts.format {
+            LogFormat::Csv => return self.render_csv(opts),
+            LogFormat::Json => return self.render_json(opts),
+            // text consumers like the tap receive the JSON equivalent of binary output
+            #[cfg(feature = "msgpack")]
+            LogFormat::MessagePack => return self.render_json(opts),
+            LogFormat::Text => (),
+        }
+
+        if let Some(ref format_fn) = self.format_fn {
+            return self.render_custom(format_fn.as_ref(), opts);
+        }
+
+        let (prefix, suffix, with_message) = self.layout(opts);
+        let message = match self.column(Field::Message) {
+            Some((width, align)) => Cow::Owned(
+                self.message
+                    .split('\n')
+                    .map(|line| fit_column(line, width, align, !suffix.is_empty()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            None => Cow::Borrowed(self.message.as_str()),
+        };
+
+        let output = if !with_message {
+            format!("{}\n", prefix.trim_end())
+        } else if let Some(width) = opts.wrap_width {
+            wrap_message(&prefix, &message, width, opts.multiline)
+        } else if opts.multiline {
+            format!("{}{}\n", prefix, indent_lines(&prefix, &message))
+        } else {
+            format!("{}{}\n", prefix, message)
+        };
+
+        let output = if suffix.is_empty() {
+            output
+        } else {
+            format!("{}{}\n", output.trim_end_matches('\n'), suffix)
+        };
+
+        if opts.color {
+            self.paint(output, &prefix)
+        } else {
+            output
+        }
+    }
+
+    // apply the line colors to output. With colors for level and module, they apply to the level
+    // and the module tag in prefix, the output before the message, or to the whole line without
+    // a prefix.
+    fn paint(&self, output: String, prefix: &str) -> String {
+        if let Some(color) = self.color {
+            return ansi_color(&output, color);
+        }
+        match self.tag_colors {
+            Some((level_color, module_color)) if !prefix.is_empty() => {
+                match output.strip_prefix(prefix) {
+                    Some(rest) => {
+                        let level = self.fit(Field::Level, self.level_head.trim_end(), true);
+                        let prefix = prefix
+                            .replacen(level.as_ref(), &ansi_color(&level, level_color), 1)
+                            .replacen(
+                                &self.module_label,
+                                &ansi_color(&self.module_label, module_color),
+                                1,
+                            );
+                        format!("{}{}", prefix, rest)
+                    }
+                    None => output,
+                }
+            }
+            Some((level_color, _)) => ansi_color(&output, level_color),
+            None => output,
+        }
+    }
+
+    // the column of field in the column layout, if any
+    fn column(&self, field: Field) -> Option<(usize, Align)> {
+        self.columns
+            .as_ref()
+            .and_then(|columns| columns.column(field))
+    }
+
+    // fit part, a field without trailing blank, to the column of field
+    fn fit<'b>(&self, field: Field, part: &'b str, pad: bool) -> Cow<'b, str> {
+        match self.column(field) {
+            Some((width, align)) => fit_column(part, width, align, pad),
+            None => Cow::Borrowed(part),
+        }
+    }
+
+    // arrange the fields of text output in field order, returns the fields before the message,
+    // the fields after the message and whether the message is shown at all
+    fn layout(&self, opts: &RenderOpts) -> (String, String, bool) {
+        let mut prefix = String::new();
+        let mut suffix = String::new();
+        let mut with_message = false;
+        let shown = |field: &Field| *field != Field::Timestamp || opts.timestamp;
+        let last = self.field_order.iter().rev().find(|field| shown(field));
+        for field in self.field_order.iter() {
+            let part = match field {
+                Field::Timestamp if opts.timestamp => &self.timestamp,
+                Field::Timestamp => "",
+                Field::Level => &self.level_head,
+                Field::Module => &self.module_head,
+                Field::Message => {
+                    with_message = true;
+                    continue;
+                }
+            };
+            let part = if self.column(*field).is_some() && shown(field) {
+                let fitted = self.fit(*field, part.trim_end(), Some(field) != last);
+                Cow::Owned(format!("{} ", fitted))
+            } else {
+                Cow::Borrowed(part)
+            };
+            if with_message {
+                if !part.is_empty() {
+                    suffix.push(' ');
+                    suffix.push_str(part.trim_end());
+                }
+            } else {
+                prefix.push_str(&part);
+            }
+        }
+        (prefix, suffix, with_message)
+    }
+
+    fn render_custom(&self, format_fn: &dyn Fn(&LogFields) -> String, opts: &RenderOpts) -> String {
+        let fields = LogFields {
+            timestamp: if opts.timestamp {
+                self.timestamp.trim_end()
+            } else {
+                ""
+            },
+            level: &self.label,
+            module: &self.module,
+            message: &self.message,
+        };
+        let output = format!("{}\n", format_fn(&fields));
+        if opts.color {
+            self.paint(output, "")
+        } else {
+            output
+        }
+    }
+
+    fn render_csv(&self, opts: &RenderOpts) -> String {
+        let timestamp = if opts.timestamp {
+            self.timestamp.trim_end()
+        } else {
+            ""
+        };
+        format!(
+            "{},{},{},{}\n",
+            csv_field(timestamp),
+            self.label,
+            csv_field(&self.module),
+            csv_field(&self.message)
+        )
+    }
+
+    fn render_json(&self, opts: &RenderOpts) -> String {
+        let fields = &self.json_fields;
+        let mut output = format!(
+            "{{{}:{},{}:{},{}:{},{}:{}",
+            json_string(&fields.timestamp),
+            json_string(if opts.timestamp {
+                self.timestamp.trim_end()
+            } else {
+                ""
+            }),
+            json_string(&fields.level),
+            json_string(&self.label),
+            json_string(&fields.module),
+            json_string(&self.module),
+            json_string(&fields.message),
+            json_string(&self.message)
+        );
+        if let Some(ref key) = fields.thread {
+            let thread = thread::current();
+            let name = thread.name().unwrap_or("<unnamed>");
+            output.push_str(&format!(",{}:{}", json_string(key), json_string(name)));
+        }
+        if let Some(ref key) = fields.pid {
+            output.push_str(&format!(",{}:{}", json_string(key), process::id()));
+        }
+        if let (Some(ref key), Some(file)) = (&fields.file, &self.file) {
+            output.push_str(&format!(",{}:{}", json_string(key), json_string(file)));
+        }
+        if let (Some(ref key), Some(line)) = (&fields.line, self.line) {
+            output.push_str(&format!(",{}:{}", json_string(key), line));
+        }
+        if let Some(seq) = self.seq {
+            output.push_str(&format!(",{}:{}", json_string(&fields.seq), seq));
+        }
+        output.push_str("}\n");
+        output
+    }
+}
+
+/// Quote and escape a JSON string
+pub(crate) fn json_string(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 2);
+    output.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => output.push(ch),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// Escape line breaks and quote the field if necessary
+fn csv_field(field: &str) -> String {
+    let field = field.replace('\r', "\\r").replace('\n', "\\n");
+    if field.contains(',') || field.contains('"') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+/// Truncate the message to max_len characters
+pub(crate) fn truncate_message(message: &mut String, max_len: usize) {
+    if let Some((index, _)) = message.char_indices().nth(max_len) {
+        message.truncate(index);
+        message.push_str(TRUNCATED_MARKER);
+    }
+}
+
+/// Indent continuation lines of a multi-line message to the start of the message and mark them
+fn indent_lines(prefix: &str, message: &str) -> String {
+    let continuation = format!(
+        "\n{}{}",
+        " ".repeat(prefix.chars().count()),
+        MULTILINE_MARKER
+    );
+    message.replace('\n', &continuation)
+}
+
+/// Word-wrap the message to width, indenting continuation lines to the start of the message
+fn wrap_message(prefix: &str, message: &str, width: usize, multiline: bool) -> String {
+    let indent = prefix.chars().count();
+    // don't bother wrapping if there is hardly any room left for the message
+    if width < indent + 20 {
+        return if multiline {
+            format!("{}{}\n", prefix, indent_lines(prefix, message))
+        } else {
+            format!("{}{}\n", prefix, message)
+        };
+    }
+
+    let continuation = if multiline {
+        format!("\n{}{}", " ".repeat(indent), MULTILINE_MARKER)
+    } else {
+        format!("\n{}", " ".repeat(indent))
+    };
+
+    let first_avail = width - indent;
+    let cont_avail = if multiline {
+        first_avail - MULTILINE_MARKER.len()
+    } else {
+        first_avail
+    };
+
+    let mut output = String::from(prefix);
+    let mut avail = first_avail;
+    for (index, line) in message.split('\n').enumerate() {
+        if index > 0 {
+            output.push_str(&continuation);
+            avail = cont_avail;
+        }
+        let mut line_len = 0;
+        for word in line.split(' ') {
+            let word_len = word.chars().count();
+            if line_len > 0 && line_len + 1 + word_len > avail {
+                output.push_str(&continuation);
+                avail = cont_avail;
+                line_len = 0;
+            } else if line_len > 0 {
+                output.push(' ');
+                line_len += 1;
+            }
+            output.push_str(word);
+            line_len += word_len;
+        }
+    }
+    output.push('\n');
+    output
+}
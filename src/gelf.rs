@@ -0,0 +1,127 @@
+use log::Level;
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{Result, ToError};
+use crate::format::{json_string, truncate_message, Line, TRUNCATED_MARKER};
+
+// the maximum size of a datagram, larger messages are chunked
+const MAX_DATAGRAM: usize = 8192;
+// magic bytes, message id, sequence number and sequence count
+const CHUNK_HEADER: usize = 12;
+// GELF allows at most 128 chunks per message
+const MAX_CHUNKS: usize = 128;
+// the largest message that fits into MAX_CHUNKS chunks
+const MAX_PAYLOAD: usize = MAX_CHUNKS * (MAX_DATAGRAM - CHUNK_HEADER);
+
+static MESSAGE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Sends records as GELF messages to a Graylog server via UDP
+pub(crate) struct GelfSender {
+    socket: UdpSocket,
+}
+
+impl GelfSender {
+    pub fn new(addr: &str) -> Result<GelfSender> {
+        let socket =
+            UdpSocket::bind("0.0.0.0:0").upstream_with_context("Failed to create GELF socket")?;
+        socket
+            .connect(addr)
+            .upstream_with_context(&format!("Failed to connect to GELF server: '{}'", addr))?;
+        Ok(GelfSender { socket })
+    }
+
+    pub fn send(&self, line: &Line, host: &str) {
+        let payload = payload(line, host);
+        let bytes = payload.as_bytes();
+        if bytes.len() <= MAX_DATAGRAM {
+            let _res = self.socket.send(bytes);
+            return;
+        }
+
+        let chunk_size = MAX_DATAGRAM - CHUNK_HEADER;
+        let chunks: Vec<&[u8]> = bytes.chunks(chunk_size).collect();
+        if chunks.len() > MAX_CHUNKS {
+            // only possible with a huge host or module name, Graylog would drop it anyway
+            return;
+        }
+        let message_id = message_id();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let mut datagram = Vec::with_capacity(CHUNK_HEADER + chunk.len());
+            datagram.extend_from_slice(&[0x1e, 0x0f]);
+            datagram.extend_from_slice(&message_id);
+            datagram.push(index as u8);
+            datagram.push(chunks.len() as u8);
+            datagram.extend_from_slice(chunk);
+            let _res = self.socket.send(&datagram);
+        }
+    }
+}
+
+// the GELF message of line, the message is truncated so the payload fits into MAX_CHUNKS
+fn payload(line: &Line, host: &str) -> String {
+    let payload = message_payload(line, host, &line.message);
+    if payload.len() <= MAX_PAYLOAD {
+        return payload;
+    }
+    // the room left for the escaped message without quotes and with the marker
+    let budget = (MAX_PAYLOAD + json_string(&line.message).len())
+        .saturating_sub(payload.len() + 2 + TRUNCATED_MARKER.len());
+    let mut used = 0;
+    let keep = line
+        .message
+        .chars()
+        .take_while(|ch| {
+            used += escaped_len(*ch);
+            used <= budget
+        })
+        .count();
+    let mut message = line.message.clone();
+    truncate_message(&mut message, keep);
+    message_payload(line, host, &message)
+}
+
+// the length of ch escaped by json_string
+fn escaped_len(ch: char) -> usize {
+    match ch {
+        '"' | '\\' | '\n' | '\r' | '\t' => 2,
+        ch if (ch as u32) < 0x20 => 6,
+        ch => ch.len_utf8(),
+    }
+}
+
+fn message_payload(line: &Line, host: &str, message: &str) -> String {
+    let timestamp = line
+        .time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or(0.0);
+    format!(
+        "{{\"version\":\"1.1\",\"host\":{},\"short_message\":{},\"level\":{},\"timestamp\":{:.3},\"_module\":{}}}",
+        json_string(host),
+        json_string(message),
+        syslog_level(line.level),
+        timestamp,
+        json_string(&line.module)
+    )
+}
+
+/// Map the log level to a syslog severity as used by GELF
+fn syslog_level(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+// a message id unique for this process, GELF only requires ids to differ between messages
+fn message_id() -> [u8; 8] {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0);
+    (nanos ^ MESSAGE_COUNT.fetch_add(1, Ordering::SeqCst).rotate_left(48)).to_be_bytes()
+}
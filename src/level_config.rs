@@ -0,0 +1,165 @@
+use log::{Level, LevelFilter};
+use std::collections::HashMap;
+use std::result;
+use std::str::FromStr;
+
+use crate::error::{Error, ErrorKind, Result, ToError};
+use crate::{Logger, DEFAULT_LOG_LEVEL};
+
+/// The module name matching all modules, setting its level sets the default level
+pub(crate) const ALL_MODULES: &str = "*";
+
+/// A set of log levels applied in one step by Logger::configure
+#[derive(Debug, Clone)]
+pub struct LevelConfig {
+    default_level: LevelFilter,
+    mod_level: HashMap<String, Level>,
+}
+
+impl<'a> LevelConfig {
+    /// Create a LevelConfig with the given default level and no module levels
+    pub fn new(default_level: Level) -> LevelConfig {
+        LevelConfig {
+            default_level: default_level.to_level_filter(),
+            mod_level: HashMap::new(),
+        }
+    }
+
+    /// Set the default log level
+    pub fn set_default_level(&'a mut self, level: Level) -> &'a mut LevelConfig {
+        self.default_level = level.to_level_filter();
+        self
+    }
+
+    /// Set the log level for a module, ```*``` sets the default level
+    pub fn set_mod_level(&'a mut self, module: &str, level: Level) -> &'a mut LevelConfig {
+        if module == ALL_MODULES {
+            self.default_level = level.to_level_filter();
+        } else {
+            let _dummy = self.mod_level.insert(String::from(module), level);
+        }
+        self
+    }
+
+    pub(crate) fn get_default_level(&self) -> LevelFilter {
+        self.default_level
+    }
+
+    pub(crate) fn get_mod_level(&'a self) -> &'a HashMap<String, Level> {
+        &self.mod_level
+    }
+}
+
+impl Default for LevelConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOG_LEVEL)
+    }
+}
+
+impl FromStr for LevelConfig {
+    type Err = Error;
+    /// Parse a comma separated list of levels, eg. ```info,app::db=debug```.\
+    /// A plain level or ```*=level``` sets the default level, ```module=level``` sets the level of
+    /// a module. Levels are given by name or as number from 0 for off to 5 for trace, only the
+    /// default level can be off.
+    fn from_str(spec: &str) -> result::Result<Self, Self::Err> {
+        let mut level_config = LevelConfig::default();
+        for directive in spec.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            let all_modules = directive
+                .split_once('=')
+                .filter(|(module, _)| module.trim() == ALL_MODULES);
+            if let Some((_, level_str)) = all_modules {
+                level_config.default_level = parse_level(level_str.trim())?;
+            } else if let Some((module, level_str)) = directive.split_once('=') {
+                // module levels can not be switched off
+                let level = parse_level(level_str.trim())
+                    .ok()
+                    .and_then(|level| level.to_level())
+                    .ok_or_else(|| {
+                        Error::with_context(
+                            ErrorKind::InvParam,
+                            &format!("invalid log level for module '{}': '{}'", module, level_str),
+                        )
+                    })?;
+                level_config.set_mod_level(module.trim(), level);
+            } else {
+                level_config.default_level = parse_level(directive)?;
+            }
+        }
+        Ok(level_config)
+    }
+}
+
+/// Restores the level of a module when dropped, see Logger::scoped_mod_level and with_level!
+#[must_use = "the previous level is restored when the guard is dropped"]
+pub struct LevelGuard {
+    module: String,
+    previous: Option<LevelFilter>,
+}
+
+impl LevelGuard {
+    pub(crate) fn new(module: &str, previous: Option<LevelFilter>) -> LevelGuard {
+        LevelGuard {
+            module: String::from(module),
+            previous,
+        }
+    }
+}
+
+impl Drop for LevelGuard {
+    fn drop(&mut self) {
+        Logger::restore_mod_level(&self.module, self.previous);
+    }
+}
+
+/// Set the level of a module while a block runs, restoring the previous level afterwards, even
+/// if the block returns early or panics.\
+/// The module is named like in Logger::set_mod_level. If the level can't be set, eg. for an
+/// unknown module in strict mode, the block runs with the levels unchanged.
+///
+/// ```
+/// use mod_logger::{with_level, Level};
+/// let rows = with_level!("app::db" => Level::Trace, {
+///     log::trace!("running query");
+///     42
+/// });
+/// assert_eq!(rows, 42);
+/// ```
+#[macro_export]
+macro_rules! with_level {
+    ($module:expr => $level:expr, $body:block) => {{
+        let _guard = $crate::Logger::scoped_mod_level($module, $level);
+        $body
+    }};
+}
+
+/// Map a numeric level to a level filter, 0 is off, 1 is error up to 5 for trace
+pub(crate) fn level_from_num(level: u8) -> Result<LevelFilter> {
+    match level {
+        0 => Ok(LevelFilter::Off),
+        1 => Ok(LevelFilter::Error),
+        2 => Ok(LevelFilter::Warn),
+        3 => Ok(LevelFilter::Info),
+        4 => Ok(LevelFilter::Debug),
+        5 => Ok(LevelFilter::Trace),
+        _ => Err(Error::with_context(
+            ErrorKind::InvParam,
+            &format!("invalid numeric log level: {}, expected 0 to 5", level),
+        )),
+    }
+}
+
+/// Parse a level given by name, eg. ```debug``` or ```off```, or numerically, see level_from_num
+pub(crate) fn parse_level(level_str: &str) -> Result<LevelFilter> {
+    if let Ok(level) = level_str.parse::<u8>() {
+        level_from_num(level)
+    } else {
+        LevelFilter::from_str(level_str).error_with_all(
+            ErrorKind::InvParam,
+            &format!("invalid log level: '{}'", level_str),
+        )
+    }
+}
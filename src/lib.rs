@@ -8,17 +8,35 @@
 //! * Log output can be colored.
 //! * Features can be set using a configuration file or the API
 //!
+//! The default level can be set by setting the environment variable ```LOG_LEVEL``` to one of
+//! trace, debug, info, warn or error. It is overridden by the configuration file and the API.
+//!
 //! The configuration file can be enabled by setting the environment variable ```LOG_CONFIG``` to the
 //! path of the file. The configuration is specified in YAML format and allows to set the following
 //! values. All values are optional.
 //!
-//! * default_level: The default log level, one of trace, debug, info, warn, error, defaults to info
-//! * mod_level: A list of module name and log level pairs
+//! With the features ```config_toml``` and ```config_json``` the configuration can also be given
+//! in TOML or JSON format. The format is derived from the file extension (```.yaml```, ```.yml```,
+//! ```.toml```, ```.json```) and can be forced by setting ```LOG_CONFIG_FORMAT``` to one of
+//! ```yaml```, ```toml``` or ```json```. Applications managing the configuration themselves can
+//! call ```Logger::disable_env_config``` before first use to ignore ```LOG_LEVEL``` and
+//! ```LOG_CONFIG```.
+//!
+//! * default_level: The default log level, one of trace, debug, info, warn, error, off, defaults to
+//!   info. Levels can also be given as number from 0 for off to 5 for trace.
+//! * mod_level: A list of module name and log level pairs, see Module names below. The module
+//!   name ```*``` stands for all modules and sets the default level, overriding default_level.
 //! * log_dest: One of stdout, stderr, stream, buffer, streamstdout, streamstderr, bufferstdout, bufferstderr.
 //! * log_stream: The log file name for stream variants of log_dest
 //! * color: one of ```true``` or ```false```
 //! * brief_info: one of ```true``` or ```false```
 //!
+//! Module names:\
+//! Modules of the executable are named by their path without the crate name, eg. ```foo``` for
+//! ```my_app::foo```, the crate root of the executable is named ```main```. Modules of all other
+//! crates are named by their full path, eg. ```dep_crate::bar```. Module levels apply to the named
+//! module and all of its submodules unless they have a level of their own.
+//!
 //! Sample:
 //! ```yaml
 //! log_level: warn
@@ -32,23 +50,46 @@
 //! ```
 //!
 
-use chrono::Local;
-use colored::*;
-use log::{Log, Metadata, Record};
-use regex::Regex;
+use chrono::{DateTime, Local};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::cell::{Cell, RefCell};
 use std::env;
+use std::fmt;
 use std::fs::File;
-#[cfg(feature = "config")]
-use std::fs::OpenOptions;
-use std::io::{stderr, stdout, BufWriter, Write};
+use std::io::Write;
 use std::mem;
-use std::sync::{Arc, Mutex, Once};
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, MutexGuard, Once, OnceLock, TryLockError};
+use std::time::{Duration, Instant, SystemTime};
+use terminal_size::{terminal_size, Width};
 
 //, BufWriter};
 mod error;
 
+mod async_writer;
+
+mod buffer;
+use buffer::reverse_lines;
+
+mod channel;
+
+mod context;
+
+pub use context::ContextGuard;
+
+#[cfg(feature = "gelf")]
+mod gelf;
+
+#[cfg(feature = "otlp")]
+mod otlp;
+
+use channel::ChannelWriter;
+
 use error::{Error, ErrorKind, Result};
 use std::path::Path;
+use std::str::FromStr;
 
 #[cfg(feature = "config")]
 pub mod config;
@@ -59,10 +100,68 @@ pub use config::LogConfig;
 #[cfg(feature = "config")]
 pub use config::LogConfigBuilder;
 
+#[cfg(feature = "config")]
+pub use config::ConfigFormat;
+
+mod level_config;
+
+use level_config::{level_from_num, parse_level};
+pub use level_config::{LevelConfig, LevelGuard};
+
+mod format;
+
+use format::{
+    abbreviate_module, format_timestamp, level_head, truncate_message, Line, DEFAULT_FIELD_ORDER,
+};
+pub use format::{
+    Align, ColumnLayout, Field, FieldNames, Formatter, LogFormat, LogMeta, ModuleTrunc,
+    OutputPreset, TimestampStyle,
+};
+
+#[doc(hidden)]
+pub mod pattern;
+
+pub use pattern::{FormatFn, LogFields};
+
+mod logger_config;
+
+pub use logger_config::LoggerConfig;
+
 mod logger_params;
 
-pub use logger_params::LogDestination;
 use logger_params::LoggerParams;
+pub use logger_params::{
+    BufferStrategy, DestinationInfo, DestinationKind, LineTap, LogDestination, RecordFilter,
+    RecordObserver, Sink,
+};
+
+mod rotation;
+
+mod sink;
+
+pub use sink::LogSink;
+
+pub use rotation::RotationConfig;
+
+mod stats;
+
+mod theme;
+
+pub use chrono::FixedOffset;
+pub use colored::Color;
+pub use regex::Regex;
+pub use theme::{Background, Theme};
+
+mod timer;
+
+use timer::Timer;
+
+mod writer;
+
+pub use writer::LogWriter;
+
+pub use stats::{LevelStats, QueueStats, SuppressionStats, TimingStats};
+use stats::{Suppression, SuppressionCounters, TimingCounters};
 
 pub(crate) const DEFAULT_LOG_LEVEL: Level = Level::Info;
 
@@ -71,11 +170,47 @@ pub(crate) const DEFAULT_LOG_DEST: LogDestination = LogDestination::Stderr;
 
 pub const NO_STREAM: Option<Box<dyn 'static + Write + Send>> = None;
 
+/// The default separator for Logger::set_run_separator
+pub const DEFAULT_RUN_SEPARATOR: &str = "===== new run at {timestamp} =====";
+
+// settings that take effect when the singleton is created, see
+// Logger::set_default_dest_before_init, Logger::new_without_registration and
+// Logger::disable_env_config
+struct InitSettings {
+    default_dest: Option<LogDestination>,
+    register: bool,
+    env_config: bool,
+}
+
+static INIT_SETTINGS: Mutex<InitSettings> = Mutex::new(InitSettings {
+    default_dest: None,
+    register: true,
+    env_config: true,
+});
+static LOGGER_CREATED: AtomicBool = AtomicBool::new(false);
+static FACADE_REGISTERED: AtomicBool = AtomicBool::new(false);
+// set once the logger has been set up completely
+static LOGGER_INITIALIZED: AtomicBool = AtomicBool::new(false);
+// set while the logger needs to see records of all levels
+static ALL_RECORDS: AtomicBool = AtomicBool::new(false);
+// the number of thread level overrides currently pushed on all threads
+static THREAD_OVERRIDES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD_LEVELS: RefCell<Vec<Level>> = const { RefCell::new(Vec::new()) };
+    // set while the record observer runs, records it logs are not observed again
+    static IN_OBSERVER: Cell<bool> = const { Cell::new(false) };
+}
+
+const AUDIT_LABEL: &str = "AUDIT";
+
+// how often the exit paths try to get the lock before giving up, see Logger::exit_lock
+const EXIT_LOCK_ATTEMPTS: usize = 10;
+
 use crate::error::ToError;
 pub use log::Level;
 
 // TODO: implement size limit for memory buffer
-// TODO: Drop initialise functions and rather use a set_config function that can repeatedly reset the configuration
 
 /// The Logger struct holds a singleton containing all relevant information.
 ///
@@ -86,6 +221,9 @@ pub struct Logger {
     inner: Arc<Mutex<LoggerParams>>,
     module_re: Regex,
     exe_name: Option<String>,
+    suppression: Arc<SuppressionCounters>,
+    timing: Arc<TimingCounters>,
+    checkpoint_timer: Arc<Timer>,
 }
 
 impl Logger {
@@ -93,13 +231,14 @@ impl Logger {
     /// The function is private, Logger is meant to be used via its static interface
     /// Any of the static functions will initialise a Logger instance
     fn new() -> Logger {
-        static mut LOGGER: *const Logger = 0 as *const Logger;
+        static mut LOGGER: *const Logger = std::ptr::null::<Logger>();
         static ONCE: Once = Once::new();
 
         // dbg!("Logger::new: entered");
 
+        // the crate name of the executable, without extension so it matches on windows too
         let exe_name = match env::current_exe() {
-            Ok(exe_name) => match exe_name.file_name() {
+            Ok(exe_name) => match exe_name.file_stem() {
                 Some(exe_name) => exe_name
                     .to_str()
                     .map(|name| name.to_owned().replace('-', "_")),
@@ -110,14 +249,28 @@ impl Logger {
 
         let logger = unsafe {
             ONCE.call_once(|| {
+                let mut params = LoggerParams::new(DEFAULT_LOG_LEVEL);
+                {
+                    let init_settings = INIT_SETTINGS.lock().unwrap();
+                    LOGGER_CREATED.store(true, Ordering::SeqCst);
+                    FACADE_REGISTERED.store(init_settings.register, Ordering::SeqCst);
+                    if let Some(ref dest) = init_settings.default_dest {
+                        // stream destinations are rejected by set_default_dest_before_init
+                        let _res = params.set_log_dest(dest, NO_STREAM);
+                    }
+                }
+
                 let singleton = Logger {
                     module_re: Regex::new(r#"^([^:]+)::(.*)$"#).unwrap(),
-                    inner: Arc::new(Mutex::new(LoggerParams::new(DEFAULT_LOG_LEVEL))),
+                    inner: Arc::new(Mutex::new(params)),
                     exe_name,
+                    suppression: Arc::new(SuppressionCounters::new()),
+                    timing: Arc::new(TimingCounters::new()),
+                    checkpoint_timer: Arc::new(Timer::new()),
                 };
 
                 // Put it in the heap so it can outlive this call
-                LOGGER = mem::transmute(Box::new(singleton));
+                LOGGER = mem::transmute::<Box<Logger>, *const Logger>(Box::new(singleton));
             });
 
             (*LOGGER).clone()
@@ -126,20 +279,50 @@ impl Logger {
         //  is initialised tests and sets the flag
         if !logger.inner.lock().unwrap().initialised() {
             // looks like we only just created it
+            let env_config = INIT_SETTINGS.lock().unwrap().env_config;
+            // LOG_LEVEL sets the default level, it is overridden by LOG_CONFIG and the API
+            if let Some(level) = env::var("LOG_LEVEL").ok().filter(|_| env_config) {
+                match parse_level(&level) {
+                    Ok(level) => {
+                        let mut guarded_params = logger.inner.lock().unwrap();
+                        guarded_params.set_default_level_filter(level);
+                        guarded_params.set_env_default_level();
+                    }
+                    Err(_why) => {
+                        eprintln!(
+                            "Invalid log level in LOG_LEVEL: '{}', using the default level {}",
+                            level, DEFAULT_LOG_LEVEL
+                        );
+                    }
+                }
+            }
+
             // look for LOG_CONFIG in ENV
             #[cfg(feature = "config")]
-            if let Ok(config_path) = env::var("LOG_CONFIG") {
+            if let Some(config_path) = env::var("LOG_CONFIG").ok().filter(|_| env_config) {
                 // eprintln!("LOG_CONFIG={}", config_path);
-                match LogConfigBuilder::from_file(&config_path) {
-                    Ok(ref log_config) => match logger.int_set_log_config(log_config.build()) {
-                        Ok(_res) => (),
-                        Err(why) => {
-                            eprintln!(
-                                "Failed to apply log config from file: '{}', error: {:?}",
-                                config_path, why
-                            );
+                let config_format = match env::var("LOG_CONFIG_FORMAT") {
+                    Ok(format) => ConfigFormat::from_str(&format),
+                    Err(_why) => Ok(ConfigFormat::from_path(Path::new(&config_path))),
+                };
+                match config_format.and_then(|format| {
+                    LogConfigBuilder::from_file_with_format(&config_path, format)
+                }) {
+                    Ok(ref log_config) => {
+                        let log_config = log_config.build();
+                        match logger.int_set_log_config(log_config, ConfigMode::Set) {
+                            Ok(_res) if log_config.get_default_level().is_some() => {
+                                logger.inner.lock().unwrap().set_env_default_level();
+                            }
+                            Ok(_res) => (),
+                            Err(why) => {
+                                eprintln!(
+                                    "Failed to apply log config from file: '{}', error: {:?}",
+                                    config_path, why
+                                );
+                            }
                         }
-                    },
+                    }
                     Err(why) => {
                         eprintln!(
                             "Failed to read log config from file: '{}', error: {:?}",
@@ -151,14 +334,18 @@ impl Logger {
 
             // potential race condition here regarding max_level
 
-            match log::set_boxed_logger(Box::new(logger.clone())) {
-                Ok(_dummy) => (),
-                Err(why) => {
-                    dbg!(why);
+            if FACADE_REGISTERED.load(Ordering::SeqCst) {
+                match log::set_boxed_logger(Box::new(logger.clone())) {
+                    Ok(_dummy) => (),
+                    Err(why) => {
+                        dbg!(why);
+                    }
                 }
+
+                update_max_level(*logger.inner.lock().unwrap().max_level());
             }
 
-            log::set_max_level(logger.inner.lock().unwrap().max_level().to_level_filter());
+            LOGGER_INITIALIZED.store(true, Ordering::SeqCst);
         }
 
         // dbg!("Logger::new: done");
@@ -166,11 +353,274 @@ impl Logger {
         logger
     }
 
+    /// Override the log destination the logger starts out with.\
+    /// This only has an effect if called before the logger is first used, eg. by logging a
+    /// message, and fails with ```ErrorKind::InvState``` otherwise. Stream destinations are not
+    /// supported as there is no stream to write to.
+    pub fn set_default_dest_before_init(dest: &LogDestination) -> Result<()> {
+        if dest.is_stream_dest() {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                &format!("Invalid default log destination: {:?}", dest),
+            ));
+        }
+
+        let mut init_settings = INIT_SETTINGS.lock().unwrap();
+        if LOGGER_CREATED.load(Ordering::SeqCst) {
+            Err(Error::with_context(
+                ErrorKind::InvState,
+                "The logger has already been initialised",
+            ))
+        } else {
+            init_settings.default_dest = Some(dest.clone());
+            Ok(())
+        }
+    }
+
+    /// Ignore the environment variables ```LOG_LEVEL``` and ```LOG_CONFIG``` when the logger is
+    /// created, for applications that manage the logger configuration themselves.\
+    /// This only has an effect if called before the logger is first used and fails with
+    /// ```ErrorKind::InvState``` otherwise, leaving the logger as it is.
+    pub fn disable_env_config() -> Result<()> {
+        let mut init_settings = INIT_SETTINGS.lock().unwrap();
+        if LOGGER_CREATED.load(Ordering::SeqCst) {
+            Err(Error::with_context(
+                ErrorKind::InvState,
+                "The logger has already been initialised",
+            ))
+        } else {
+            init_settings.env_config = false;
+            Ok(())
+        }
+    }
+
+    /// Create the logger without registering it with the log crate.\
+    /// The returned Logger implements log::Log, so records can be fed to it by another
+    /// log implementation. The static interface configures this instance as usual but leaves
+    /// the max level of the log crate alone. Fails with ```ErrorKind::InvState``` if the logger
+    /// has already been initialised.
+    pub fn new_without_registration() -> Result<Logger> {
+        {
+            let mut init_settings = INIT_SETTINGS.lock().unwrap();
+            if LOGGER_CREATED.load(Ordering::SeqCst) {
+                return Err(Error::with_context(
+                    ErrorKind::InvState,
+                    "The logger has already been initialised",
+                ));
+            }
+            init_settings.register = false;
+        }
+        Ok(Logger::new())
+    }
+
+    /// Check if the logger has been initialized without creating it.\
+    /// Returns true once the logger has been created and registered with the log crate, or
+    /// created by new_without_registration. Libraries can use this to skip logging setup when
+    /// the application has not set up the logger, as any other function of the static interface
+    /// creates it.
+    pub fn is_initialized() -> bool {
+        LOGGER_INITIALIZED.load(Ordering::SeqCst)
+    }
+
+    /// Log panics as error records.\
+    /// Installs a panic hook that logs the panic message and location through the logger and
+    /// then calls the previously installed hook. Panics happening while the logger is locked,
+    /// eg. in a record filter or format function, are not logged.
+    pub fn install_panic_hook() {
+        let logger = Logger::new();
+        let prev_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let payload = info.payload();
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.as_str()
+            } else {
+                "Box<dyn Any>"
+            };
+            let thread = std::thread::current();
+            let thread_name = thread.name().unwrap_or("<unnamed>");
+
+            // the panic may have happened while the lock was held, so don't wait for it
+            if let Some(mut guarded_params) = logger.exit_lock() {
+                logger.log_locked(
+                    &mut guarded_params,
+                    &Record::builder()
+                        .level(Level::Error)
+                        .target("panic")
+                        .module_path(Some("panic"))
+                        .file(info.location().map(|location| location.file()))
+                        .line(info.location().map(|location| location.line()))
+                        .args(format_args!(
+                            "thread '{}' panicked at {}: {}",
+                            thread_name,
+                            info.location()
+                                .map(|location| location.to_string())
+                                .unwrap_or_else(|| String::from("<unknown>")),
+                            message
+                        ))
+                        .build(),
+                    false,
+                );
+                logger.final_flush(&mut guarded_params);
+            }
+
+            prev_hook(info);
+        }));
+    }
+
+    /// Flush all sinks when the process receives SIGTERM or SIGINT.\
+    /// After flushing, the default action of the signal is performed, so the process still
+    /// terminates. This is opt-in as it installs handlers for these signals.
+    #[cfg(feature = "signal")]
+    pub fn flush_on_signal() -> Result<()> {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+        use signal_hook::iterator::Signals;
+        use signal_hook::low_level::emulate_default_handler;
+
+        let mut signals = Signals::new([SIGTERM, SIGINT])
+            .upstream_with_context("Failed to install signal handlers")?;
+        let logger = Logger::new();
+        std::thread::spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                logger.exit_flush();
+                let _res = emulate_default_handler(signal);
+            }
+        });
+        Ok(())
+    }
+
+    /// Write the contents of the memory buffer to path when the process exits.\
+    /// The buffer is written when main returns, std::process::exit is called or, with
+    /// Logger::flush_on_signal, on SIGTERM and SIGINT, so the last records captured survive an
+    /// unexpected exit. The file is only written if a buffer destination is active at that time.
+    /// ```None``` disables the dump. Fails with ```ErrorKind::InvState``` if the exit handler
+    /// can't be registered.
+    pub fn set_buffer_dump_on_exit(path: Option<&Path>) -> Result<()> {
+        static REGISTERED: OnceLock<bool> = OnceLock::new();
+
+        extern "C" {
+            fn atexit(callback: extern "C" fn()) -> std::os::raw::c_int;
+        }
+
+        // must not unwind into the C runtime, a panic is caught and the dump skipped.
+        // No summary is logged, the thread locals needed to format it are already destroyed.
+        extern "C" fn on_exit() {
+            let _res = panic::catch_unwind(|| {
+                let logger = Logger::new();
+                let guarded_params = logger.exit_lock();
+                if let Some(mut guarded_params) = guarded_params {
+                    if guarded_params.buffer_dump().is_some() {
+                        guarded_params.flush();
+                        if let Err(why) = guarded_params.dump_buffer() {
+                            eprintln!("{}", why);
+                        }
+                    }
+                }
+            });
+        }
+
+        if path.is_some() {
+            // the handler is registered once, it does nothing while no dump path is set
+            if !*REGISTERED.get_or_init(|| unsafe { atexit(on_exit) == 0 }) {
+                return Err(Error::with_context(
+                    ErrorKind::InvState,
+                    "Failed to register the exit handler",
+                ));
+            }
+        }
+
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_buffer_dump(path);
+        Ok(())
+    }
+
     /// Flush the contents of log buffers
     pub fn flush() {
         Logger::new().flush();
     }
 
+    /// Flush the contents of log buffers and report whether output was written.\
+    /// Returns true if output was written to a log file, stream or sink since the last flush and
+    /// all of them were flushed successfully, eg. for tests waiting for log output to land in a
+    /// file. Output written to the console is not taken into account.
+    pub fn flush_checked() -> bool {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.flush()
+    }
+
+    /// Flush and close all sinks and reset the logger to its defaults, eg. for the teardown of
+    /// tests sharing a process.\
+    /// The log buffer, module levels and destinations, filters, taps and all other settings are
+    /// dropped, the logger stays registered and logs to stderr at the default level info.
+    /// The logger is a global singleton, so tests relying on clear_all must not run in parallel
+    /// with other tests using the logger. Thread levels and contexts are thread local and
+    /// are not affected.
+    pub fn clear_all() {
+        let logger = Logger::new();
+        logger.checkpoint_timer.stop();
+        {
+            let mut guarded_params = logger.inner.lock().unwrap();
+            guarded_params.flush();
+            let mut params = LoggerParams::new(DEFAULT_LOG_LEVEL);
+            // the environment is only evaluated for a new logger
+            let _initialised = params.initialised();
+            *guarded_params = params;
+            ALL_RECORDS.store(guarded_params.needs_all_records(), Ordering::SeqCst);
+            update_max_level(*guarded_params.max_level());
+        }
+        logger.suppression.reset();
+        logger.timing.set_enabled(false);
+        logger.timing.reset();
+    }
+
+    /// Write a line formatted elsewhere, eg. by another logging system, to the log destination
+    /// and all sinks.\
+    /// The line is written as it is, without timestamp, level or module, and colored in the color
+    /// of level if color is enabled. It is not subject to level filtering, but counted in
+    /// Logger::level_stats. Redactions set by Logger::add_redaction are applied. With
+    /// LogFormat::MessagePack the line is encoded as the message of a record with level only.
+    pub fn write_raw(level: Level, line: &str) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        let mut line = String::from(line.trim_end_matches(['\r', '\n']));
+        guarded_params.redact(&mut line);
+        let line = Line {
+            level,
+            label: level.to_string(),
+            color: guarded_params.raw_line_color(level),
+            tag_colors: None,
+            module_label: String::new(),
+            timestamp: String::new(),
+            #[cfg(any(feature = "gelf", feature = "otlp"))]
+            time: SystemTime::now(),
+            module: String::new(),
+            level_head: String::new(),
+            module_head: String::new(),
+            field_order: guarded_params.field_order(),
+            message: line.clone(),
+            format_fn: None,
+            formatted: Some(line),
+            file: None,
+            line: None,
+            seq: None,
+            json_fields: guarded_params.json_fields(),
+            columns: None,
+        };
+        let opts = guarded_params.sink_opts();
+        guarded_params.write_line(&line, &opts);
+    }
+
+    /// Get a handle that writes raw output to the log destination, eg. to forward the output of a
+    /// subprocess to the same sinks as the log.\
+    /// Output written to the handle is not formatted or filtered. Each write holds the logger lock,
+    /// so it never interleaves with log lines. Flushing the handle flushes the logger.
+    pub fn writer_handle() -> LogWriter {
+        LogWriter::new(Logger::new().inner.clone())
+    }
+
     /// create a default logger
     pub fn create() {
         let _logger = Logger::new();
@@ -185,8 +635,65 @@ impl Logger {
         let max_level = guarded_params.set_default_level(log_level);
 
         if last_max_level != max_level {
-            log::set_max_level(max_level.to_level_filter());
+            update_max_level(max_level);
+        }
+    }
+
+    /// Set the default level according to the build profile, Debug for builds with debug
+    /// assertions and Info otherwise.\
+    /// A default level given by the environment variable ```LOG_LEVEL``` or the config file named
+    /// by ```LOG_CONFIG``` takes precedence and is kept. Otherwise this replaces the default
+    /// level like Logger::set_default_level, later API calls and configs override it.
+    pub fn set_default_level_for_profile() {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        if guarded_params.env_default_level() {
+            return;
+        }
+        let level = if cfg!(debug_assertions) {
+            Level::Debug
+        } else {
+            Level::Info
+        };
+        let last_max_level = *guarded_params.max_level();
+        let max_level = guarded_params.set_default_level(level);
+
+        if last_max_level != max_level {
+            update_max_level(max_level);
+        }
+    }
+
+    /// Set the default level numerically, eg. from a script.\
+    /// 0 switches the default level off, 1 to 5 stand for error, warn, info, debug and trace.
+    /// Modules with a module level are still logged at their level while the default level is
+    /// off. Values above 5 fail with ```ErrorKind::InvParam```.
+    pub fn set_default_level_num(log_level: u8) -> Result<()> {
+        let level = level_from_num(log_level)?;
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        let last_max_level = *guarded_params.max_level();
+        let max_level = guarded_params.set_default_level_filter(level);
+
+        if last_max_level != max_level {
+            update_max_level(max_level);
         }
+        Ok(())
+    }
+
+    /// Set the default level from a verbosity count, eg. the number of ```-v``` flags minus the
+    /// number of ```-q``` flags.\
+    /// Starting from info, positive values increase and negative values decrease the
+    /// verbosity, clamped to the range error to trace.
+    pub fn set_verbosity(verbosity: i32) {
+        const LEVELS: [Level; 5] = [
+            Level::Error,
+            Level::Warn,
+            Level::Info,
+            Level::Debug,
+            Level::Trace,
+        ];
+        let index = (2 + verbosity as i64).clamp(0, LEVELS.len() as i64 - 1) as usize;
+        Logger::set_default_level(LEVELS[index]);
     }
 
     /// Retrieve the default level of the logger
@@ -195,294 +702,1824 @@ impl Logger {
         guarded_params.get_default_level()
     }
 
-    /// Modify the log level for a module
+    /// Modify the log level for a module.\
+    /// Modules of the executable are named without the crate name, the crate root as ```main```,
+    /// see the crate documentation. ```*``` stands for all modules and sets the default level,
+    /// it is equivalent to Logger::set_default_level and the last one applied wins. In strict
+    /// mode levels for modules that were not registered are not set and the error is printed to
+    /// stderr, use Logger::try_set_mod_level to handle it.
     pub fn set_mod_level(module: &str, log_level: Level) {
+        if let Err(why) = Logger::try_set_mod_level(module, log_level) {
+            eprintln!(
+                "Failed to set the log level of module '{}', error: {}",
+                module, why
+            );
+        }
+    }
+
+    /// Modify the log level for a module like Logger::set_mod_level.\
+    /// In strict mode setting a level for a module that was not registered fails with
+    /// ```ErrorKind::InvParam```.
+    pub fn try_set_mod_level(module: &str, log_level: Level) -> Result<()> {
         let logger = Logger::new();
         let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.check_module(module)?;
         let last_max_level = *guarded_params.max_level();
         let max_level = guarded_params.set_mod_level(module, log_level);
         if last_max_level != *max_level {
-            log::set_max_level(max_level.to_level_filter());
+            update_max_level(*max_level);
         }
+        Ok(())
     }
 
-    /// Retrieve the current log buffer, if available
-    pub fn get_buffer() -> Option<Vec<u8>> {
+    /// Set the log level of a module until the returned guard is dropped, which restores the
+    /// previous level of the module, see with_level!.\
+    /// Fails like Logger::try_set_mod_level.
+    pub fn scoped_mod_level(module: &str, log_level: Level) -> Result<LevelGuard> {
         let logger = Logger::new();
         let mut guarded_params = logger.inner.lock().unwrap();
-        guarded_params.retrieve_log_buffer()
+        guarded_params.check_module(module)?;
+        let previous = guarded_params.exact_mod_level(module);
+        let last_max_level = *guarded_params.max_level();
+        let max_level = guarded_params.set_mod_level(module, log_level);
+        if last_max_level != *max_level {
+            update_max_level(*max_level);
+        }
+        Ok(LevelGuard::new(module, previous))
     }
 
-    /// Set the log destination
-    pub fn set_log_dest<S: 'static + Write + Send>(
-        dest: &LogDestination,
-        stream: Option<S>,
-    ) -> Result<()> {
+    /// Restore the level of module for a LevelGuard
+    pub(crate) fn restore_mod_level(module: &str, level: Option<LevelFilter>) {
+        let logger = Logger::new();
+        // the guard may be dropped while unwinding from a panic that poisoned the lock
+        let mut guarded_params = logger
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let last_max_level = *guarded_params.max_level();
+        let max_level = guarded_params.restore_mod_level(module, level);
+        if last_max_level != *max_level {
+            update_max_level(*max_level);
+        }
+    }
+
+    /// Enable / disable strict module checking.\
+    /// In strict mode module levels can only be set for modules registered with
+    /// Logger::register_modules or their parents, so typos in module names are caught when the
+    /// levels are set instead of silently never matching. This applies to Logger::set_mod_level,
+    /// Logger::configure, Logger::apply and to log configs.
+    pub fn set_strict_modules(strict: bool) {
         let logger = Logger::new();
-        logger.flush();
         let mut guarded_params = logger.inner.lock().unwrap();
-        guarded_params.set_log_dest(dest, stream)
+        guarded_params.set_strict_modules(strict)
     }
 
-    /// Set log destination  and log file.
-    pub fn set_log_file(log_dest: &LogDestination, log_file: &Path, buffered: bool) -> Result<()> {
-        let dest = if log_dest.is_stdout() {
-            LogDestination::StreamStdout
-        } else if log_dest.is_stderr() {
-            LogDestination::StreamStderr
-        } else {
-            LogDestination::Stream
-        };
+    /// Register the names of known modules for strict module checking, see
+    /// Logger::set_strict_modules. Module names are given as for Logger::set_mod_level.
+    pub fn register_modules(modules: &[&str]) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.register_modules(modules)
+    }
 
-        let mut stream: Box<dyn Write + Send> = if buffered {
-            Box::new(BufWriter::new(
-                File::create(log_file).upstream_with_context(&format!(
-                    "Failed to create file: '{}'",
-                    log_file.display()
-                ))?,
-            ))
-        } else {
-            Box::new(File::create(log_file).upstream_with_context(&format!(
-                "Failed to create file: '{}'",
-                log_file.display()
-            ))?)
-        };
+    /// Set the default level and the given module levels in one step.\
+    /// Module levels not contained in levels are left unchanged. In strict mode nothing is changed
+    /// if levels contains a module that was not registered, this fails with
+    /// ```ErrorKind::InvParam```.
+    pub fn configure(levels: LevelConfig) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.check_level_config(&levels)?;
+        let last_max_level = *guarded_params.max_level();
+        let max_level = guarded_params.set_level_config(&levels);
+        if last_max_level != *max_level {
+            update_max_level(*max_level);
+        }
+        Ok(())
+    }
 
+    /// Explain which rule determines the effective level of module, eg.
+    /// ```app::db: Error (exact module override), default is Debug```.\
+    /// module is given as in the output, modules of the executable are looked up without the crate
+    /// name like in Logger::set_mod_level. The thread level, if any, is the one of the calling
+    /// thread.
+    pub fn explain_level(module: &str) -> String {
         let logger = Logger::new();
-        logger.flush();
+        let (mod_name, mod_tag) = logger.mod_names(module);
+        let guarded_params = logger.inner.lock().unwrap();
+        let default_level = guarded_params.default_level_filter();
 
-        let mut guarded_params = logger.inner.lock().unwrap();
-        let buffer = guarded_params.retrieve_log_buffer();
+        let (mut level, mut explanation) = match guarded_params.find_mod_level(&mod_tag) {
+            Some((path, level)) if path == mod_tag => (
+                level.to_level_filter(),
+                format!(
+                    "{:?} (exact module override), default is {:?}",
+                    level, default_level
+                ),
+            ),
+            Some((path, level)) => (
+                level.to_level_filter(),
+                format!(
+                    "{:?} (inherited from {}), default is {:?}",
+                    level, path, default_level
+                ),
+            ),
+            None => (
+                default_level,
+                format!("{:?} (default level)", default_level),
+            ),
+        };
 
-        if let Some(buffer) = buffer {
-            stream
-                .write_all(buffer.as_slice())
-                .upstream_with_context(&format!(
-                    "Failed to write buffers to file: '{}'",
-                    log_file.display()
-                ))?;
-            stream.flush().upstream_with_context(&format!(
-                "Failed to flush buffers to file: '{}'",
-                log_file.display()
-            ))?;
+        if let Some(external_level) = guarded_params.external_level() {
+            let prefix = guarded_params
+                .internal_prefix()
+                .or(logger.exe_name.as_deref());
+            if !is_internal(&mod_name, prefix) && level > external_level {
+                explanation = format!(
+                    "{:?} (capped for external modules), otherwise {}",
+                    external_level, explanation
+                );
+                level = external_level.to_level_filter();
+            }
+        }
+
+        if let Some(thread_level) = THREAD_LEVELS.with(|levels| levels.borrow().last().copied()) {
+            if thread_level != level {
+                explanation = format!(
+                    "{:?} (thread level of the current thread), otherwise {}",
+                    thread_level, explanation
+                );
+            }
         }
 
-        guarded_params.set_log_dest(&dest, Some(stream))
+        format!("{}: {}", mod_name, explanation)
     }
 
-    /// Retrieve the current log destination
-    pub fn get_log_dest() -> LogDestination {
+    /// Override the log level for the current thread.\
+    /// While an override is pushed, records logged on this thread are filtered by the most recently
+    /// pushed level instead of the default, module and external levels. Other threads are not
+    /// affected.
+    pub fn push_thread_level(level: Level) {
+        THREAD_LEVELS.with(|levels| levels.borrow_mut().push(level));
         let logger = Logger::new();
         let guarded_params = logger.inner.lock().unwrap();
-        guarded_params.get_log_dest().clone()
+        THREAD_OVERRIDES.fetch_add(1, Ordering::SeqCst);
+        update_max_level(*guarded_params.max_level());
     }
 
-    /// Set the log configuration.
-    #[cfg(feature = "config")]
-    pub fn set_log_config(log_config: &LogConfig) -> Result<()> {
-        Logger::new().int_set_log_config(log_config)
+    /// Remove the most recently pushed level override of the current thread and return it
+    pub fn pop_thread_level() -> Option<Level> {
+        let level = THREAD_LEVELS.with(|levels| levels.borrow_mut().pop());
+        if level.is_some() {
+            let logger = Logger::new();
+            let guarded_params = logger.inner.lock().unwrap();
+            THREAD_OVERRIDES.fetch_sub(1, Ordering::SeqCst);
+            update_max_level(*guarded_params.max_level());
+        }
+        level
     }
 
-    /// Enable / disable colored output
-    pub fn set_color(color: bool) {
+    /// Remember the current time under name, eg. ```startup```, see Logger::set_show_since.\
+    /// Marking a name again moves the marker to the current time.
+    pub fn mark(name: &str) {
         let logger = Logger::new();
         let mut guarded_params = logger.inner.lock().unwrap();
-        guarded_params.set_color(color)
+        guarded_params.mark(name)
     }
 
-    /// Enable / disable timestamp in messages
-    pub fn set_timestamp(val: bool) {
+    /// Append the time elapsed since the marker name to every message, eg.
+    /// ```(+1.234s since startup)```. Nothing is appended until the marker is set by
+    /// Logger::mark. ```None``` disables this, which is the default.
+    pub fn set_show_since(name: Option<&str>) {
         let logger = Logger::new();
         let mut guarded_params = logger.inner.lock().unwrap();
-        guarded_params.set_timestamp(val)
+        guarded_params.set_show_since(name)
     }
 
-    /// Enable / disable timestamp in messages
-    pub fn set_millis(val: bool) {
-        let logger = Logger::new();
-        let mut guarded_params = logger.inner.lock().unwrap();
-        guarded_params.set_millis(val)
+    /// Push a context label for the current thread.\
+    /// While labels are pushed, records logged on this thread are prefixed with the labels as
+    /// breadcrumb, eg. ```[request:42 > db > query] message```. Logger::enter_context removes the
+    /// label automatically.
+    pub fn push_context(label: &str) {
+        let _logger = Logger::new();
+        context::push(label);
     }
 
-    /// Enable / disable brief info messages
-    pub fn set_brief_info(val: bool) {
-        let logger = Logger::new();
-        let mut guarded_params = logger.inner.lock().unwrap();
-        guarded_params.set_brief_info(val)
+    /// Remove the most recently pushed context label of the current thread and return it
+    pub fn pop_context() -> Option<String> {
+        context::pop()
     }
 
-    #[cfg(feature = "config")]
-    fn int_set_log_config(&self, log_config: &LogConfig) -> Result<()> {
-        let mut guarded_params = self.inner.lock().unwrap();
-        let last_max_level = *guarded_params.max_level();
+    /// Push a context label for the current thread that is removed when the returned guard is
+    /// dropped, see Logger::push_context.
+    pub fn enter_context(label: &str) -> ContextGuard {
+        let _logger = Logger::new();
+        ContextGuard::new(label)
+    }
 
-        guarded_params.set_default_level(log_config.get_default_level());
+    /// Apply a complete configuration.\
+    /// Levels, destination, format and the other settings covered by LoggerConfig are replaced
+    /// by the values of config under one lock. Fails without changing anything if a stream
+    /// destination is given without log file, the log file can't be opened, the format can't be
+    /// written to the destination or, in strict mode, if the levels contain a module that was
+    /// not registered.
+    pub fn apply(config: LoggerConfig) -> Result<()> {
+        let wrap_width = match config.get_wrap_width() {
+            Some(0) => terminal_size().map(|(Width(width), _)| width as usize),
+            width => width,
+        };
 
-        let max_level = guarded_params.set_mod_config(log_config.get_mod_level());
-        if max_level != &last_max_level {
-            log::set_max_level(max_level.to_level_filter());
-        }
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
 
-        let log_dest = guarded_params.get_log_dest();
-        let cfg_log_dest = log_config.get_log_dest();
-        let stream_log = cfg_log_dest.is_stream_dest();
-
-        if cfg_log_dest != log_dest || stream_log {
-            if stream_log {
-                if let Some(log_stream) = log_config.get_log_stream() {
-                    guarded_params.set_log_dest(
-                        cfg_log_dest,
-                        Some(
-                            OpenOptions::new()
-                                .append(true)
-                                .create(true)
-                                .open(log_stream)
-                                .upstream_with_context(&format!(
-                                    "Failed to open log file: '{}'",
-                                    log_stream.display()
-                                ))?,
-                        ),
-                    )?;
-                } else {
-                    return Err(Error::with_context(
-                        ErrorKind::InvParam,
-                        &format!(
-                            "Missing parameter log_stream for destination {:?}",
-                            cfg_log_dest
-                        ),
-                    ));
-                }
+        // validate config and open the log file before anything is changed
+        guarded_params.check_level_config(config.get_levels())?;
+        let log_dest = config.get_log_dest();
+        LoggerParams::check_format(config.get_format(), log_dest)?;
+        let log_file = if log_dest.is_stream_dest() {
+            if let Some(log_file) = config.get_log_file() {
+                Some((log_file, LoggerParams::open_file(log_file, false)?))
             } else {
-                guarded_params.set_log_dest(cfg_log_dest, NO_STREAM)?;
+                return Err(Error::with_context(
+                    ErrorKind::InvParam,
+                    &format!("Missing log file for destination {:?}", log_dest),
+                ));
             }
-        }
+        } else {
+            None
+        };
 
-        guarded_params.set_color(log_config.is_color());
-        guarded_params.set_brief_info(log_config.is_brief_info());
+        // a binary format can't be kept while switching to the console
+        if guarded_params.format().is_binary() && log_dest.is_console() {
+            guarded_params.set_format(LogFormat::Text);
+        }
+        match log_file {
+            Some((path, file)) => guarded_params.log_to_file(log_dest, path, file, None, None)?,
+            None => guarded_params.set_log_dest(log_dest, NO_STREAM)?,
+        }
 
+        let max_level = *guarded_params.replace_level_config(config.get_levels());
+        update_max_level(max_level);
+        guarded_params.set_format(config.get_format());
+        guarded_params.set_color(config.get_color());
+        guarded_params.set_timestamp(config.get_timestamp());
+        guarded_params.set_millis(config.get_millis());
+        guarded_params.set_brief_info(config.get_brief_info());
+        guarded_params.set_timezone(config.get_timezone());
+        guarded_params.set_wrap_width(wrap_width);
+        guarded_params.set_max_message_len(config.get_max_message_len());
         Ok(())
     }
-}
 
-impl Log for Logger {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    /// Cap the log level of all external modules.\
+    /// Records from modules that do not start with the internal prefix are logged at most at the
+    /// given level. If no internal prefix has been set, the modules of the executable are
+    /// considered internal. ```None``` removes the cap.
+    pub fn set_external_level(log_level: Option<Level>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_external_level(log_level)
     }
 
-    fn log(&self, record: &Record) {
-        let (mod_name, mod_tag) = if let Some(mod_path) = record.module_path() {
-            if let Some(ref exe_name) = self.exe_name {
-                if let Some(ref captures) = self.module_re.captures(mod_path) {
-                    if captures.get(1).unwrap().as_str() == exe_name {
-                        (
-                            mod_path.to_owned(),
-                            captures.get(2).unwrap().as_str().to_owned(),
-                        )
-                    } else {
-                        (mod_path.to_owned(), mod_path.to_owned())
-                    }
-                } else if mod_path == exe_name {
-                    (mod_path.to_owned(), String::from("main"))
-                } else {
-                    (mod_path.to_owned(), mod_path.to_owned())
-                }
-            } else {
-                (mod_path.to_owned(), mod_path.to_owned())
-            }
-        } else {
-            (String::from("undefined"), String::from("undefined"))
-        };
-
-        let curr_level = record.metadata().level();
+    /// Set the module prefix that identifies internal modules, eg. the name of your crate.
+    /// ```None``` falls back to the executable name.
+    pub fn set_internal_prefix(prefix: Option<&str>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_internal_prefix(prefix)
+    }
 
+    /// Limit the number of records logged per second by a single log statement.\
+    /// Records are throttled by their call site, the source file and line, rather than by their
+    /// message, so a log statement in a hot loop is limited even if every message differs.
+    /// Each call site may log bursts of up to per_sec records, dropped records are counted as
+    /// ```rate_limited``` in Logger::suppression_stats. Audit records and records without
+    /// source location are not limited, 0 disables the limit, which is the default.
+    pub fn set_callsite_rate_limit(per_sec: u32) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_callsite_rate_limit(per_sec)
+    }
+
+    /// Retrieve the number of records suppressed by rate limiting
+    pub fn suppression_stats() -> SuppressionStats {
+        Logger::new().suppression.snapshot()
+    }
+
+    /// Reset the suppression statistics
+    pub fn reset_suppression_stats() {
+        Logger::new().suppression.reset()
+    }
+
+    /// Enable / disable measuring the time spent logging records.\
+    /// When enabled, the time spent filtering, formatting and writing every record is
+    /// accumulated, see Logger::timing_stats. This helps to decide whether async mode or a lower
+    /// verbosity is worthwhile. Disabled by default, which costs nothing.
+    pub fn set_self_timing(val: bool) {
+        Logger::new().timing.set_enabled(val)
+    }
+
+    /// Retrieve the time spent logging records while self timing was enabled
+    pub fn timing_stats() -> TimingStats {
+        Logger::new().timing.snapshot()
+    }
+
+    /// Reset the timing statistics
+    pub fn reset_timing_stats() {
+        Logger::new().timing.reset()
+    }
+
+    /// Enable / disable the async mode for log files.\
+    /// In async mode output to log files is queued and written by a background thread, so
+    /// logging does not wait for the file system. The queue holds up to capacity records, records
+    /// are dropped if the queue is full, see Logger::async_queue_stats. Flushing the logger waits
+    /// until all queued records are written. ```None``` writes synchronously, which is the
+    /// default. The mode applies to log files opened after the call. Fails with
+    /// ```ErrorKind::InvParam``` if capacity is 0.
+    pub fn set_async(capacity: Option<usize>) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_async_capacity(capacity)
+    }
+
+    /// Retrieve the number of records waiting in the async queue
+    pub fn async_queue_depth() -> usize {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.queue_counters().depth()
+    }
+
+    /// Retrieve depth, high water mark and dropped records of the async queue
+    pub fn async_queue_stats() -> QueueStats {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.queue_counters().snapshot()
+    }
+
+    /// Reset high water mark and dropped records of the async queue
+    pub fn reset_async_queue_stats() {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.queue_counters().reset()
+    }
+
+    /// Set a function that decides whether a record is logged.\
+    /// The filter is called for every record that passed the level checks, returning false drops
+    /// the record. Audit records are not filtered. The filter runs while the logger is locked, so
+    /// it must be fast and must not log itself.
+    pub fn set_filter(filter: RecordFilter) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_record_filter(Some(filter));
+    }
+
+    /// Remove the filter set with Logger::set_filter
+    pub fn clear_filter() {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_record_filter(None);
+    }
+
+    /// Set a function that receives every line right before it is written, eg. for golden output
+    /// tests or to mirror the log.\
+    /// The line is passed as rendered for the first sink of the log destination, including color
+    /// and the line terminator. The tap runs while the logger is locked, so it must be fast and
+    /// must not log itself.
+    pub fn set_tap(tap: LineTap) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_line_tap(Some(tap));
+    }
+
+    /// Remove the tap set with Logger::set_tap
+    pub fn clear_tap() {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_line_tap(None);
+    }
+
+    /// Keep the last context_lines records below the log level and write them right before a
+    /// record at trigger level or above, eg. to see the debug output that led up to an error.\
+    /// Kept records are subject to the record filter and are written with their original
+    /// timestamp. All records are passed to the logger while replay is enabled. A context_lines
+    /// value of 0 disables replay, as does Logger::clear_replay_on_error.
+    pub fn set_replay_on_error(trigger: Level, context_lines: usize) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_replay(Some((trigger, context_lines)));
+        ALL_RECORDS.store(guarded_params.needs_all_records(), Ordering::SeqCst);
+        update_max_level(*guarded_params.max_level());
+    }
+
+    /// Disable replay set with Logger::set_replay_on_error and drop the kept records
+    pub fn clear_replay_on_error() {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_replay(None);
+        ALL_RECORDS.store(guarded_params.needs_all_records(), Ordering::SeqCst);
+        update_max_level(*guarded_params.max_level());
+    }
+
+    /// Set a function that is called with level and module of every record, before the record
+    /// is filtered by level or formatted.\
+    /// All records are passed to the logger while an observer is set, so keep the observer
+    /// cheap. The observer is called without holding the logger lock, so it may log itself,
+    /// records logged by the observer are not passed to it again. ```None``` removes the observer.
+    pub fn set_record_observer(observer: Option<RecordObserver>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_record_observer(observer);
+        ALL_RECORDS.store(guarded_params.needs_all_records(), Ordering::SeqCst);
+        update_max_level(*guarded_params.max_level());
+    }
+
+    /// Set the target of audit records.\
+    /// Records logged with this target, eg. ```info!(target: "audit", ...)```, are always
+    /// logged regardless of their level and are labeled ```AUDIT```. While an audit target is set
+    /// all records are passed to the logger. ```None``` disables audit records.
+    pub fn set_audit_target(target: Option<&str>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_audit_target(target);
+        ALL_RECORDS.store(guarded_params.needs_all_records(), Ordering::SeqCst);
+        update_max_level(*guarded_params.max_level());
+    }
+
+    /// Set a dedicated stream for audit records.\
+    /// Audit records are written to the stream instead of the log destination, ```None```
+    /// restores writing them to the log destination.
+    pub fn set_audit_stream<S: 'static + Write + Send>(stream: Option<S>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_audit_stream(stream)
+    }
+
+    /// Send the records of module and its submodules to a destination of their own, eg. a
+    /// dedicated file for ```app::audit```.\
+    /// The destination is one of ```LogDestination::Stdout```, ```LogDestination::Stderr``` or
+    /// ```LogDestination::Stream``` with the given stream. The records are also written to the
+    /// log destination if also_global is true. module is named like in Logger::set_mod_level.
+    pub fn set_mod_dest<S: 'static + Write + Send>(
+        module: &str,
+        dest: &LogDestination,
+        stream: Option<S>,
+        also_global: bool,
+    ) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.check_module(module)?;
+        guarded_params.set_mod_dest(module, dest, stream, also_global)
+    }
+
+    /// Remove the destination of module set with Logger::set_mod_dest
+    pub fn clear_mod_dest(module: &str) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.clear_mod_dest(module)
+    }
+
+    /// Add a sink that receives every line written to the log destination, eg. a database or an
+    /// uploader implemented by the application.\
+    /// Sinks are identified by their name, adding a second sink with the same name fails with
+    /// ```ErrorKind::InvParam```. Sinks are kept when the log destination changes.
+    pub fn add_sink(sink: Box<dyn LogSink>) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.add_sink(sink)
+    }
+
+    /// Replace all sinks added by Logger::add_sink at once, eg. to move to a new set of sinks
+    /// without a window in which lines go nowhere or to half of the sinks.\
+    /// Returns the previous sinks, flushed, for the caller to close. Fails with
+    /// ```ErrorKind::InvParam``` if two of the new sinks have the same name, the current sinks
+    /// stay in place then.
+    pub fn swap_destinations(sinks: Vec<Box<dyn LogSink>>) -> Result<Vec<Box<dyn LogSink>>> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.swap_sinks(sinks)
+    }
+
+    /// Flush and remove the sink named name, returns false if there is no such sink
+    pub fn remove_sink(name: &str) -> bool {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.remove_sink(name)
+    }
+
+    /// Retrieve the current log buffer, if available
+    pub fn get_buffer() -> Option<Vec<u8>> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.retrieve_log_buffer()
+    }
+
+    /// Retrieve the current log buffer with the newest line first, if available.\
+    /// Like Logger::get_buffer the buffer is emptied. Every returned line ends with a line break,
+    /// even if the last line in the buffer did not.
+    pub fn get_buffer_reversed() -> Option<Vec<u8>> {
+        Logger::get_buffer().map(|buffer| reverse_lines(&buffer))
+    }
+
+    /// Set the log destination
+    pub fn set_log_dest<S: 'static + Write + Send>(
+        dest: &LogDestination,
+        stream: Option<S>,
+    ) -> Result<()> {
+        let logger = Logger::new();
+        logger.flush();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_log_dest(dest, stream)
+    }
+
+    /// Periodically write the contents of the memory buffer to a file.\
+    /// Every interval the file is overwritten with the current buffer contents, so that recent
+    /// log output survives a crash. The buffer itself is left unchanged.
+    pub fn set_buffer_checkpoint(path: &Path, interval: Duration) -> Result<()> {
+        // make sure the file can be written before starting the timer
+        File::create(path).upstream_with_context(&format!(
+            "Failed to create checkpoint file: '{}'",
+            path.display()
+        ))?;
+
+        let logger = Logger::new();
+        let inner = logger.inner.clone();
+        let path = path.to_path_buf();
+        logger.checkpoint_timer.start(interval, move || {
+            let buffer = inner.lock().unwrap().buffer_contents();
+            if let Some(buffer) = buffer {
+                let _res = File::create(&path).and_then(|mut file| file.write_all(&buffer));
+            }
+        });
+        Ok(())
+    }
+
+    /// Stop writing buffer checkpoints
+    pub fn clear_buffer_checkpoint() {
+        Logger::new().checkpoint_timer.stop();
+    }
+
+    /// Set a function formatting the text log lines, see the log_format! macro.\
+    /// The function receives the fields of the record and returns the line without line break.
+    /// ```None``` restores the default format. CSV and JSON output is not affected.
+    pub fn set_format_fn(format_fn: Option<FormatFn>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_format_fn(format_fn)
+    }
+
+    /// Set a function formatting records instead of the built-in formats.\
+    /// The function receives the record and its resolved parts and returns the line without line
+    /// break. It is used for all sinks and output formats, colors are applied to text output.
+    /// With LogFormat::MessagePack the line is encoded as the message of the record.
+    /// ```None``` restores the built-in formats.
+    pub fn set_formatter(formatter: Option<Formatter>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_formatter(formatter)
+    }
+
+    /// Send each formatted log line to a channel owned by the caller.\
+    /// If the receiver is disconnected the logger falls back to stderr.
+    pub fn set_channel_dest(sender: Sender<String>) -> Result<()> {
+        Logger::set_log_dest(&LogDestination::Stream, Some(ChannelWriter::new(sender)))
+    }
+
+    /// Use storage as a fixed size ring buffer for the memory buffer, capping its memory use.\
+    /// Once the buffer is full the oldest output is overwritten, so the buffer may start in the
+    /// middle of a line. The buffer never grows or reallocates, Logger::get_buffer returns the
+    /// contents oldest first. The current buffer contents are moved to storage as far as they
+    /// fit. The ring buffer is kept when switching between buffer destinations. Fails with
+    /// ```ErrorKind::InvState``` if the log destination has no buffer and with
+    /// ```ErrorKind::InvParam``` if storage is empty.
+    pub fn set_fixed_buffer(storage: Box<[u8]>) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_fixed_buffer(storage)
+    }
+
+    /// Change the capacity of the fixed size buffer set with Logger::set_fixed_buffer, eg. to
+    /// keep more context during an incident.\
+    /// The contents are kept, when shrinking the buffer the oldest output is dropped. Fails with
+    /// ```ErrorKind::InvState``` if the log destination has no fixed size buffer and with
+    /// ```ErrorKind::InvParam``` if capacity is 0.
+    pub fn resize_buffer(capacity: usize) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.resize_buffer(capacity)
+    }
+
+    /// Write the contents of the memory buffer to a file without changing the log destination.\
+    /// If clear is true the buffer is emptied afterwards, otherwise buffering continues with the
+    /// current contents. Fails if the log destination has no buffer.
+    pub fn dump_buffer_to(path: &Path, clear: bool) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        let buffer = if clear {
+            guarded_params.retrieve_log_buffer()
+        } else {
+            guarded_params.buffer_contents()
+        };
+
+        if let Some(buffer) = buffer {
+            let mut file = File::create(path)
+                .upstream_with_context(&format!("Failed to create file: '{}'", path.display()))?;
+            file.write_all(buffer.as_slice())
+                .upstream_with_context(&format!(
+                    "Failed to write buffer to file: '{}'",
+                    path.display()
+                ))
+        } else {
+            Err(Error::with_context(
+                ErrorKind::InvState,
+                &format!(
+                    "log destination {:?} has no buffer to dump",
+                    guarded_params.get_log_dest()
+                ),
+            ))
+        }
+    }
+
+    /// Set how log files opened by the logger are buffered, eg. BufferStrategy::LineBuffered to
+    /// follow the log with ```tail -f``` without a system call per write.\
+    /// This applies to log files opened afterwards, including rotated and reopened files.
+    /// Defaults to BufferStrategy::Unbuffered. Fails with ```ErrorKind::InvParam``` for a block
+    /// size of 0.
+    pub fn set_file_buffering(strategy: BufferStrategy) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_file_buffering(strategy)
+    }
+
+    /// Set log destination  and log file.\
+    /// A buffered log file uses a BufWriter with default capacity, otherwise the file is
+    /// buffered as set by Logger::set_file_buffering.
+    pub fn set_log_file(log_dest: &LogDestination, log_file: &Path, buffered: bool) -> Result<()> {
+        // the default capacity of BufWriter
+        let buffering = buffered.then_some(BufferStrategy::Block(8 * 1024));
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.open_log_file(&stream_dest(log_dest), log_file, true, buffering, None)
+    }
+
+    /// Log everything to log_file and keep the most recent capacity bytes of the output in a ring
+    /// buffer, eg. for a view of the recent log in the application.\
+    /// The buffer is read with Logger::get_buffer and resized with Logger::resize_buffer, the
+    /// file is not affected by either. Setting another log destination drops the buffer.
+    /// Fails with ```ErrorKind::InvParam``` if capacity is 0.
+    pub fn set_buffer_and_file(capacity: usize, log_file: &Path) -> Result<()> {
+        if capacity == 0 {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                "the buffer capacity must not be 0",
+            ));
+        }
+        Logger::set_log_file(&LogDestination::Stream, log_file, false)?;
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_file_buffer(capacity)
+    }
+
+    /// Set log destination and a log file rotated by size.\
+    /// The log file is opened in append mode and rotated as described by rotation. Fails with
+    /// ```ErrorKind::InvParam``` if max_bytes of rotation is 0. If rotating fails, logging
+    /// continues to the file and rotation is retried after a minute.
+    pub fn set_rotating_log_file(
+        log_dest: &LogDestination,
+        log_file: &Path,
+        rotation: &RotationConfig,
+    ) -> Result<()> {
+        if rotation.get_max_bytes() == 0 {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                "The maximum size of a rotated log file must be greater than 0",
+            ));
+        }
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.open_log_file(
+            &stream_dest(log_dest),
+            log_file,
+            false,
+            None,
+            Some(rotation.clone()),
+        )
+    }
+
+    /// Retrieve the number of bytes written to the log file or stream since it was opened or
+    /// last rotated, None if the log destination is not a stream destination.
+    pub fn current_file_bytes() -> Option<u64> {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.stream_written().map(|(bytes, _)| bytes)
+    }
+
+    /// Retrieve the number of lines written to the log file or stream since it was opened or
+    /// last rotated, None if the log destination is not a stream destination.
+    pub fn current_file_lines() -> Option<u64> {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.stream_written().map(|(_, lines)| lines)
+    }
+
+    /// Rotate the log file now.\
+    /// The current log file is flushed and renamed, logging continues to a new file at the same
+    /// path. With a rotation config, see set_rotating_log_file, the rotated files are shifted as
+    /// they are for size based rotation, otherwise the file is renamed to ```<file>.<timestamp>```.
+    /// Fails with ```ErrorKind::InvState``` if the log destination is not a log file.
+    pub fn rotate() -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.rotate_file()
+    }
+
+    /// Enable / disable reopening the log file when it is renamed or removed by another
+    /// program, eg. logrotate without copytruncate.\
+    /// The path of the log file is checked at most once per second while logging, a new file is
+    /// created if the path no longer refers to the open file. Disabled by default.
+    pub fn set_reopen_on_rotate(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_reopen_on_rotate(val)
+    }
+
+    /// Rotate the log file whenever the process receives SIGHUP, see rotate
+    #[cfg(feature = "signal")]
+    pub fn rotate_on_sighup() -> Result<()> {
+        use signal_hook::consts::SIGHUP;
+        use signal_hook::iterator::Signals;
+
+        let mut signals =
+            Signals::new([SIGHUP]).upstream_with_context("Failed to install signal handler")?;
+        std::thread::spawn(move || {
+            for _signal in signals.forever() {
+                if let Err(why) = Logger::rotate() {
+                    eprintln!("Failed to rotate log file, error: {:?}", why);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Set log destination and a log file that is created on the first write.\
+    /// Unlike set_log_file no file is left behind if nothing is logged. If the memory buffer holds
+    /// log output, the file is created immediately to receive it.
+    pub fn set_lazy_log_file(log_dest: &LogDestination, log_file: &Path) -> Result<()> {
+        let logger = Logger::new();
+        let buffered = {
+            let mut guarded_params = logger.inner.lock().unwrap();
+            guarded_params.flush();
+            // a buffer holding a copy of the log file does not need to be written
+            !guarded_params.buffer_with_file()
+                && guarded_params
+                    .buffer_contents()
+                    .is_some_and(|buffer| !buffer.is_empty())
+        };
+        if buffered {
+            return Logger::set_log_file(log_dest, log_file, false);
+        }
+
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_lazy_log_file(&stream_dest(log_dest), log_file)
+    }
+
+    /// Set the log destination from its string representation, see LogDestination::from_str.\
+    /// For stream destinations the log file given by stream_path is opened in append mode.
+    pub fn set_log_dest_str(dest: &str, stream_path: Option<&Path>) -> Result<()> {
+        let dest = LogDestination::from_str(dest)?;
+        if dest.is_stream_dest() {
+            if let Some(stream_path) = stream_path {
+                let logger = Logger::new();
+                let mut guarded_params = logger.inner.lock().unwrap();
+                guarded_params.open_log_file(&dest, stream_path, false, None, None)
+            } else {
+                Err(Error::with_context(
+                    ErrorKind::InvParam,
+                    &format!("Missing parameter stream_path for destination {:?}", dest),
+                ))
+            }
+        } else {
+            Logger::set_log_dest(&dest, NO_STREAM)
+        }
+    }
+
+    /// Initialize the logger from a single spec string.\
+    /// The spec is a level spec as parsed by LevelConfig::from_str, optionally followed by
+    /// ```@<dest>``` or, for stream destinations, ```@<dest>:<path>```, eg.
+    /// ```info,app::db=debug@stderr``` or ```warn@streamstderr:/tmp/app.log```. The spec is split
+    /// at the first ```@```, so the path may contain ```@```.
+    pub fn init(spec: &str) -> Result<()> {
+        let (level_spec, dest_spec) = match spec.split_once('@') {
+            Some((level_spec, dest_spec)) => (level_spec, Some(dest_spec)),
+            None => (spec, None),
+        };
+
+        Logger::configure(LevelConfig::from_str(level_spec)?)?;
+        if let Some(dest_spec) = dest_spec {
+            match dest_spec.split_once(':') {
+                Some((dest, path)) => Logger::set_log_dest_str(dest, Some(Path::new(path)))?,
+                None => Logger::set_log_dest_str(dest_spec, None)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Retrieve the current log destination
+    pub fn get_log_dest() -> LogDestination {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.get_log_dest().clone()
+    }
+
+    /// List the destinations the logger currently writes to
+    pub fn list_destinations() -> Vec<DestinationInfo> {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.list_destinations()
+    }
+
+    /// Describe the current configuration of the logger as pairs of setting name and value
+    pub fn describe() -> Vec<(String, String)> {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.describe()
+    }
+
+    /// Set the log configuration.\
+    /// The module levels of log_config are added to the module levels already set, use
+    /// set_config to replace them.
+    #[cfg(feature = "config")]
+    pub fn set_log_config(log_config: &LogConfig) -> Result<()> {
+        Logger::new().int_set_log_config(log_config, ConfigMode::Set)
+    }
+
+    /// Overlay log_config on the current configuration.\
+    /// Module levels of log_config are added or override existing ones, all other settings are
+    /// only changed if they are set in log_config. This allows layered configurations, eg. a
+    /// base configuration with environment specific overrides.
+    #[cfg(feature = "config")]
+    pub fn merge_config(log_config: &LogConfig) -> Result<()> {
+        Logger::new().int_set_log_config(log_config, ConfigMode::Merge)
+    }
+
+    /// Replace the log configuration.\
+    /// Levels, destination and flags are replaced by the values of log_config, module levels not
+    /// contained in log_config are removed. This can be called any number of times, a log file
+    /// that is already open for the configured destination is kept open.
+    #[cfg(feature = "config")]
+    pub fn set_config(log_config: &LogConfig) -> Result<()> {
+        Logger::new().int_set_log_config(log_config, ConfigMode::Replace)
+    }
+
+    /// Enable / disable colored output.\
+    /// Output to the memory buffer is never colored. On Windows ANSI escape sequences are enabled
+    /// on the console when color is enabled for a console destination, if the console does not
+    /// support them color stays disabled.
+    pub fn set_color(color: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_color(color)
+    }
+
+    /// Check if colored output is enabled
+    pub fn is_color() -> bool {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.color()
+    }
+
+    /// Set the color used for messages of the given level
+    pub fn set_level_color(level: Level, color: Color) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_level_color(level, color)
+    }
+
+    /// Color records by module instead of by level, so lines of the same module are easy to
+    /// group visually. Each module is mapped to a stable color of the module palette.\
+    /// While records are also colored by level, see Logger::set_color_by_level, the module color
+    /// applies to the module tag and the level color to the level, otherwise the whole line is
+    /// colored by module. Disabled by default.
+    pub fn set_color_by_module(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_color_by_module(val)
+    }
+
+    /// Enable / disable coloring records by level, enabled by default.\
+    /// With coloring by level and by module disabled, colored output shows no colors.
+    pub fn set_color_by_level(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_color_by_level(val)
+    }
+
+    /// Set the colors modules are mapped to by Logger::set_color_by_module.\
+    /// Fails with ```ErrorKind::InvParam``` if palette is empty.
+    pub fn set_module_palette(palette: &[Color]) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_module_palette(palette)
+    }
+
+    /// Apply a color theme.\
+    /// Theme::Monochrome disables colored output, all other themes set the level colors and
+    /// enable colored output.
+    pub fn set_theme(theme: Theme) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        if let Some(colors) = theme.level_colors() {
+            guarded_params.set_level_colors(colors);
+            guarded_params.set_color(true);
+        } else {
+            guarded_params.set_color(false);
+        }
+    }
+
+    /// Apply a consistent set of display settings in one step.\
+    /// The preset sets color, timestamps, milliseconds, the timestamp format and time zone, the
+    /// output format, brief info, source locations, module truncation and the field order.
+    /// Individual settings can be changed afterwards.
+    pub fn set_preset(preset: OutputPreset) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        let (color, timestamp, millis, format) = match preset {
+            OutputPreset::Development => (true, true, true, LogFormat::Text),
+            OutputPreset::Production => (false, true, true, LogFormat::Json),
+            OutputPreset::Minimal => (false, false, false, LogFormat::Text),
+        };
+        guarded_params.set_color(color);
+        guarded_params.set_timestamp(timestamp);
+        for sink in [Sink::Console, Sink::Stream, Sink::Buffer] {
+            guarded_params.set_sink_timestamp(sink, (!timestamp).then_some(false));
+        }
+        guarded_params.set_millis(millis);
+        guarded_params.set_format(format);
+        guarded_params.set_brief_info(false);
+        guarded_params.set_adaptive_format(false);
+        guarded_params.set_show_location(preset == OutputPreset::Development);
+        guarded_params.set_module_truncate(ModuleTrunc::Full);
+
+        let production = preset == OutputPreset::Production;
+        guarded_params.set_timestamp_style(if production {
+            TimestampStyle::Rfc3339
+        } else {
+            TimestampStyle::DateTime
+        });
+        guarded_params.set_timezone(production.then(|| FixedOffset::east_opt(0).unwrap()));
+
+        let fields: &[Field] = if preset == OutputPreset::Minimal {
+            &[Field::Level, Field::Message]
+        } else {
+            &DEFAULT_FIELD_ORDER
+        };
+        let _res = guarded_params.set_field_order(fields);
+    }
+
+    /// Enable / disable showing the source location of all records, eg. ```src/main.rs:12: ```.\
+    /// With adaptive format the location of debug and trace records is shown regardless.
+    pub fn set_show_location(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_show_location(val)
+    }
+
+    /// Enable / disable RFC 3339 timestamps, eg. ```2020-01-01T12:00:00.123Z```.\
+    /// Milliseconds are included if enabled with Logger::set_millis. This is a shorthand for
+    /// Logger::set_timestamp_style with TimestampStyle::Rfc3339, disabling it returns to
+    /// TimestampStyle::DateTime.
+    pub fn set_rfc3339(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_rfc3339(val)
+    }
+
+    /// Set the style of timestamps, eg. TimestampStyle::TimeOnly to leave out the date in
+    /// interactive sessions. Defaults to TimestampStyle::DateTime.\
+    /// Milliseconds are included in all styles if enabled with Logger::set_millis, the timezone
+    /// set with Logger::set_timezone applies to all styles showing the time of day. The style
+    /// applies to all sinks, Logger::set_sink_timestamp controls which sinks are timestamped.
+    pub fn set_timestamp_style(style: TimestampStyle) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_timestamp_style(style)
+    }
+
+    /// Set level colors that are readable on the given terminal background.\
+    /// Background::detect can be used to find the background of the terminal, eg.
+    /// ```Logger::set_background(Background::detect().unwrap_or(Background::Dark))```.
+    pub fn set_background(background: Background) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_level_colors(background.level_colors())
+    }
+
+    /// Enable / disable timestamp in console messages.\
+    /// Output to the stream and the memory buffer is always timestamped so archived records are
+    /// self-describing, use ```set_sink_timestamp``` to change that.
+    pub fn set_timestamp(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_timestamp(val)
+    }
+
+    /// Check if console messages are timestamped
+    pub fn is_timestamp() -> bool {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.timestamp()
+    }
+
+    /// Override the timestamp setting for a sink.\
+    /// ```None``` restores the default, the console follows the setting of ```set_timestamp```,
+    /// the stream and the memory buffer are timestamped.
+    pub fn set_sink_timestamp(sink: Sink, include_timestamp: Option<bool>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_sink_timestamp(sink, include_timestamp)
+    }
+
+    /// Enable / disable milliseconds in timestamps
+    pub fn set_millis(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_millis(val)
+    }
+
+    /// Check if timestamps contain milliseconds
+    pub fn is_millis() -> bool {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.millis()
+    }
+
+    /// Replace matches of pattern in all messages, eg. to scrub tokens or email addresses.\
+    /// Redactions are applied to every message before it is written to any sink, in the order
+    /// they were added. Each redaction costs a regex search per message, so keep patterns simple
+    /// when logging at a high rate. The replacement may refer to capture groups, eg. ```$1```.
+    pub fn add_redaction(pattern: Regex, replacement: &str) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.add_redaction(pattern, replacement)
+    }
+
+    /// Remove all redactions
+    pub fn clear_redactions() {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.clear_redactions()
+    }
+
+    /// Only log records within a time window.\
+    /// Records logged before start or after stop are dropped, ```None``` leaves the window open at
+    /// that end. The window uses monotonic time, so it is not affected by changes of the system
+    /// clock.
+    pub fn set_active_window(start: Option<Instant>, stop: Option<Instant>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_active_window(start, stop)
+    }
+
+    /// Set the host name reported by network destinations, eg. the pod name in a container.\
+    /// Defaults to the host name detected when the logger is created.
+    pub fn set_hostname(hostname: &str) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_hostname(hostname)
+    }
+
+    /// Set a separator line written when a log file is opened in append mode, so the start of
+    /// each run is easy to find. ```{timestamp}``` in the separator is replaced by the current
+    /// time, see DEFAULT_RUN_SEPARATOR. The separator is not written to empty files.
+    /// ```None``` disables the separator, which is the default. Note that the separator is not
+    /// valid CSV or JSON.
+    pub fn set_run_separator(separator: Option<String>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_run_separator(separator)
+    }
+
+    /// Set the time zone of timestamps, ```None``` uses the local time zone
+    pub fn set_timezone(timezone: Option<FixedOffset>) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_timezone(timezone)
+    }
+
+    /// Enable / disable brief info messages
+    pub fn set_brief_info(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_brief_info(val)
+    }
+
+    /// Check if brief info messages are enabled
+    pub fn is_brief_info() -> bool {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.brief_info()
+    }
+
+    /// Set the width at which messages written to the console are word-wrapped.\
+    /// ```Some(0)``` uses the width of the terminal detected when this function is called,
+    /// ```None``` disables wrapping. Log files and buffers always receive single lines.
+    pub fn set_wrap_width(width: Option<usize>) {
+        let width = match width {
+            Some(0) => terminal_size().map(|(Width(width), _)| width as usize),
+            width => width,
+        };
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_wrap_width(width)
+    }
+
+    /// Enable / disable the adaptive format.\
+    /// The adaptive format chooses the format by level: trace and debug messages contain module
+    /// and source location, info messages are brief, warnings and errors contain the module.
+    pub fn set_adaptive_format(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_adaptive_format(val)
+    }
+
+    /// Enable / disable normalizing line endings in messages.\
+    /// Trailing line breaks are removed from messages and ```\r\n``` within messages is replaced
+    /// by ```\n```, so every record ends with exactly one line break.
+    pub fn set_normalize_newlines(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_normalize_newlines(val)
+    }
+
+    /// Enable / disable indenting continuation lines of multi-line messages.\
+    /// Continuation lines are aligned with the start of the message and marked with ```| ```.
+    pub fn set_indent_multiline(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_indent_multiline(val)
+    }
+
+    /// Set the maximum length of messages in characters, longer messages are truncated.\
+    /// ```0``` means unlimited.
+    pub fn set_max_message_len(max_len: usize) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_max_message_len(max_len)
+    }
+
+    /// Set the order of the fields in text output, eg.
+    /// ```[Field::Level, Field::Timestamp, Field::Message]```.\
+    /// Fields not contained in fields are not printed. The default order is timestamp, level,
+    /// module, message. The order does not apply to CSV and JSON output or to custom formats.
+    /// Fails with ```ErrorKind::InvParam``` if fields is empty or a field is given more than once.
+    pub fn set_field_order(fields: &[Field]) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_field_order(fields)
+    }
+
+    /// Set the output format.\
+    /// With LogFormat::Csv a header line is written to the current destination and to every
+    /// log file or buffer subsequently set up. Color is not applied to CSV, JSON and MessagePack
+    /// output. No run separator, CSV or banner header is written to MessagePack streams.
+    /// A format the log destination can't show, ie. LogFormat::MessagePack on the console, is
+    /// not set and the error is printed to stderr, use Logger::try_set_format to handle it.
+    pub fn set_format(format: LogFormat) {
+        if let Err(why) = Logger::try_set_format(format) {
+            eprintln!("Failed to set the log format, error: {}", why);
+        }
+    }
+
+    /// Set the output format like Logger::set_format.\
+    /// Fails with ```ErrorKind::InvParam``` for LogFormat::MessagePack if the log destination
+    /// writes to the console, as does setting such a destination while it is in use.
+    pub fn try_set_format(format: LogFormat) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        LoggerParams::check_format(format, guarded_params.get_log_dest())?;
+        guarded_params.set_format(format);
+        Ok(())
+    }
+
+    /// Set the keys used by LogFormat::Json and the optional fields to include.\
+    /// Fails with ```ErrorKind::InvParam``` if a key is used for more than one field, including
+    /// the key of the sequence number while records are not numbered.
+    pub fn set_json_field_names(fields: FieldNames) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_json_fields(fields)
+    }
+
+    /// Lay out text output in fixed width columns, eg. to get neatly aligned console output.\
+    /// Fields shorter than their column are padded, longer ones are truncated with ```…```,
+    /// at the end of left aligned and at the start of right aligned columns. Columns apply to
+    /// text output only and are applied after Logger::set_module_truncate and
+    /// Logger::set_module_max_len. Fails with ```ErrorKind::InvParam``` for a column width of 0.
+    pub fn set_column_layout(columns: ColumnLayout) -> Result<()> {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_column_layout(Some(columns))
+    }
+
+    /// Remove the column layout, fields keep their natural width again
+    pub fn clear_column_layout() {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        let _res = guarded_params.set_column_layout(None);
+    }
+
+    /// Set how much of the module path is shown in text output, eg. ```app``` instead of
+    /// ```app::db::pool``` with ModuleTrunc::TopLevel. Defaults to ModuleTrunc::Full.\
+    /// This applies to all levels, CSV and JSON output always contain the full module path.
+    pub fn set_module_truncate(module_trunc: ModuleTrunc) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_module_truncate(module_trunc)
+    }
+
+    /// Enable / disable tagging every written record with a sequence number, eg. ```#000123```
+    /// in text output or as ```seq``` field in JSON output.\
+    /// The number increases by one for every record written, so gaps reveal records dropped by
+    /// rate limiting or a full async queue. Records kept by Logger::set_replay_on_error are
+    /// numbered when they are replayed. Numbering continues where it left off when re-enabled.
+    pub fn set_show_seq(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_show_seq(val)
+    }
+
+    /// Abbreviate module paths longer than max_len characters in text output by replacing
+    /// middle segments with ```…```, eg. ```app::…::query``` for ```app::db::pool::query```.\
+    /// The first and the last segment are always kept. This applies after
+    /// Logger::set_module_truncate, 0 disables abbreviation, which is the default.
+    pub fn set_module_max_len(max_len: usize) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_module_max_len(max_len)
+    }
+
+    /// Set the strings enclosing the module name in messages, defaults to ```[``` and ```]```.
+    /// Use empty strings for no brackets.
+    pub fn set_module_brackets(open: &str, close: &str) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_module_brackets(open, close)
+    }
+
+    /// Enable / disable showing the target of records.\
+    /// The target is shown after the module name if it differs from the module path, ie. if it
+    /// was set explicitly, eg. ```info!(target: "net", ...)```.
+    pub fn set_show_target(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_show_target(val)
+    }
+
+    /// Log message only the first time it is logged with key during the lifetime of the process.\
+    /// The message is attributed to the main module of the executable, use the log_once macro to
+    /// attribute it to the calling module.
+    pub fn log_once(level: Level, key: &str, message: &str) {
+        let logger = Logger::new();
+        let first = logger.inner.lock().unwrap().mark_once(key);
+        if first {
+            logger.log_own(level, message, false);
+        }
+    }
+
+    /// Log a record of module only the first time key is logged, see the log_once macro
+    #[doc(hidden)]
+    pub fn log_once_at(
+        level: Level,
+        key: &str,
+        module: &'static str,
+        file: &'static str,
+        line: u32,
+        args: fmt::Arguments,
+    ) {
+        let logger = Logger::new();
+        let first = logger.inner.lock().unwrap().mark_once(key);
+        if first {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(module)
+                    .module_path_static(Some(module))
+                    .file_static(Some(file))
+                    .line(Some(line))
+                    .args(args)
+                    .build(),
+            );
+        }
+    }
+
+    /// Forget all keys seen by log_once
+    pub fn reset_once() {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.reset_once()
+    }
+
+    /// Retrieve the number of records written per level
+    pub fn level_stats() -> LevelStats {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.level_counters().snapshot()
+    }
+
+    /// Reset the number of records written per level
+    pub fn reset_level_stats() {
+        let logger = Logger::new();
+        let guarded_params = logger.inner.lock().unwrap();
+        guarded_params.level_counters().reset()
+    }
+
+    /// Log a summary of the warnings and errors written so far at info level, eg.
+    /// ```completed with 3 warnings, 1 error```.\
+    /// Only records that were written count, records dropped by level or filter do not. The
+    /// summary is logged regardless of the log level, attributed to the main module of the
+    /// executable.
+    pub fn log_summary() {
+        let logger = Logger::new();
+        logger.log_own(Level::Info, &Logger::level_stats().summary(), true);
+    }
+
+    /// Enable / disable logging the summary of Logger::log_summary when the logger is flushed
+    /// at the end of the process, ie. by the panic hook or the signal handler installed with
+    /// Logger::flush_on_signal. Logger::flush and the exit handler of
+    /// Logger::set_buffer_dump_on_exit do not log the summary.
+    pub fn set_summary_on_flush(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_summary_on_flush(val)
+    }
+
+    /// Log a banner identifying the application and its version at info level.\
+    /// The banner is logged regardless of the log level, attributed to the main module of the
+    /// executable. It is remembered and, if enabled by ```set_banner_header```, written as a
+    /// header line to every log file subsequently opened.
+    pub fn log_banner(app: &str, version: &str) {
+        let banner = format!("{} version {}", app, version);
+        let logger = Logger::new();
+        logger.inner.lock().unwrap().set_banner(&banner);
+        logger.log_own(Level::Info, &banner, true);
+    }
+
+    /// Enable / disable writing the banner as header line to newly opened log files
+    pub fn set_banner_header(val: bool) {
+        let logger = Logger::new();
+        let mut guarded_params = logger.inner.lock().unwrap();
+        guarded_params.set_banner_header(val)
+    }
+
+    /// Flush the logger at the end of the process, logging the summary first if enabled
+    fn final_flush(&self, guarded_params: &mut LoggerParams) {
+        if guarded_params.summary_on_flush() {
+            let summary = guarded_params.level_counters().snapshot().summary();
+            self.log_locked(
+                guarded_params,
+                &self.own_record(Level::Info, format_args!("{}", summary)),
+                true,
+            );
+        }
+        guarded_params.flush();
+    }
+
+    /// Flush the logger as the process exits and dump the buffer if enabled.\
+    /// Nothing is flushed if the lock can't be taken, see exit_lock.
+    #[cfg(feature = "signal")]
+    fn exit_flush(&self) {
+        if let Some(mut guarded_params) = self.exit_lock() {
+            self.final_flush(&mut guarded_params);
+            if let Err(why) = guarded_params.dump_buffer() {
+                eprintln!("{}", why);
+            }
+        }
+    }
+
+    /// Resolve the module name shown in the output and the module tag used to look up levels.\
+    /// Modules of the executable are tagged without the crate name, the crate itself as ```main```.
+    fn mod_names(&self, mod_path: &str) -> (String, String) {
+        if let Some(ref exe_name) = self.exe_name {
+            if let Some(ref captures) = self.module_re.captures(mod_path) {
+                if captures.get(1).unwrap().as_str() == exe_name {
+                    (
+                        mod_path.to_owned(),
+                        captures.get(2).unwrap().as_str().to_owned(),
+                    )
+                } else {
+                    (mod_path.to_owned(), mod_path.to_owned())
+                }
+            } else if mod_path == exe_name {
+                (mod_path.to_owned(), String::from("main"))
+            } else {
+                (mod_path.to_owned(), mod_path.to_owned())
+            }
+        } else {
+            (mod_path.to_owned(), mod_path.to_owned())
+        }
+    }
+
+    #[cfg(feature = "config")]
+    fn int_set_log_config(&self, log_config: &LogConfig, mode: ConfigMode) -> Result<()> {
         let mut guarded_params = self.inner.lock().unwrap();
-        let mut level = guarded_params.get_default_level();
-        if let Some(mod_level) = guarded_params.get_mod_level(&mod_tag) {
-            level = mod_level;
+        for module in log_config.get_mod_level().keys() {
+            guarded_params.check_module(module)?;
+        }
+        let last_max_level = *guarded_params.max_level();
+
+        // when merging only the settings present in log_config are applied, otherwise unset
+        // settings take their defaults
+        let merge = mode == ConfigMode::Merge;
+
+        match log_config.get_default_level() {
+            Some(level) => {
+                guarded_params.set_default_level_filter(level);
+            }
+            None if !merge => {
+                guarded_params.set_default_level(DEFAULT_LOG_LEVEL);
+            }
+            None => (),
         }
 
-        if curr_level <= level {
-            let timestamp = if guarded_params.timestamp() {
-                let now = Local::now();
-                if guarded_params.millis() {
-                    let ts_millis = now.timestamp_millis() % 1000;
-                    format!("{}.{:03} ", now.format("%Y-%m-%d %H:%M:%S"), ts_millis)
+        if mode == ConfigMode::Replace {
+            guarded_params.clear_mod_level();
+        }
+        let max_level = guarded_params.set_mod_config(log_config.get_mod_level());
+        if max_level != &last_max_level {
+            update_max_level(*max_level);
+        }
+
+        let cfg_log_dest = match log_config.get_log_dest() {
+            Some(log_dest) => Some(log_dest),
+            None if !merge => Some(&DEFAULT_LOG_DEST),
+            None => None,
+        };
+
+        if let Some(cfg_log_dest) = cfg_log_dest {
+            let log_dest = guarded_params.get_log_dest();
+            let stream_log = cfg_log_dest.is_stream_dest();
+            // keep the log file if it is already open
+            let same_stream = stream_log
+                && cfg_log_dest == log_dest
+                && guarded_params.log_path() == log_config.get_log_stream().as_deref();
+
+            if (cfg_log_dest != log_dest || stream_log) && !same_stream {
+                if stream_log {
+                    if let Some(log_stream) = log_config.get_log_stream() {
+                        guarded_params.open_log_file(
+                            cfg_log_dest,
+                            log_stream,
+                            false,
+                            None,
+                            None,
+                        )?;
+                    } else {
+                        return Err(Error::with_context(
+                            ErrorKind::InvParam,
+                            &format!(
+                                "Missing parameter log_stream for destination {:?}",
+                                cfg_log_dest
+                            ),
+                        ));
+                    }
                 } else {
-                    format!("{} ", now.format("%Y-%m-%d %H:%M:%S"))
+                    guarded_params.set_log_dest(cfg_log_dest, NO_STREAM)?;
+                }
+            }
+        }
+
+        if let Some(color) = log_config.is_color().or((!merge).then_some(false)) {
+            guarded_params.set_color(color);
+        }
+        if let Some(brief_info) = log_config.is_brief_info().or((!merge).then_some(false)) {
+            guarded_params.set_brief_info(brief_info);
+        }
+
+        Ok(())
+    }
+}
+
+/// How a LogConfig is applied to the logger
+#[cfg(feature = "config")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ConfigMode {
+    /// add module levels, reset unset settings to their defaults
+    Set,
+    /// replace module levels, reset unset settings to their defaults
+    Replace,
+    /// add module levels, leave unset settings alone
+    Merge,
+}
+
+/// Set the max level of the log crate, unless the logger is not registered with it.\
+/// Record observers, audit records and thread level overrides need to see all records so the max
+/// level is opened up while they are in use.
+fn update_max_level(max_level: Level) {
+    if FACADE_REGISTERED.load(Ordering::SeqCst) {
+        if ALL_RECORDS.load(Ordering::SeqCst) || THREAD_OVERRIDES.load(Ordering::SeqCst) > 0 {
+            log::set_max_level(Level::Trace.to_level_filter());
+        } else {
+            log::set_max_level(max_level.to_level_filter());
+        }
+    }
+}
+
+/// The stream destination that also logs to the console of log_dest, if any
+fn stream_dest(log_dest: &LogDestination) -> LogDestination {
+    if log_dest.is_stdout() {
+        LogDestination::StreamStdout
+    } else if log_dest.is_stderr() {
+        LogDestination::StreamStderr
+    } else {
+        LogDestination::Stream
+    }
+}
+
+/// Remove trailing line breaks and replace CRLF by LF
+fn normalize_newlines(message: &mut String) {
+    let len = message.trim_end_matches(['\r', '\n']).len();
+    message.truncate(len);
+    if message.contains('\r') {
+        *message = message.replace("\r\n", "\n");
+    }
+}
+
+/// Check if module is the module given by prefix or one of its submodules
+fn is_internal(module: &str, prefix: Option<&str>) -> bool {
+    if let Some(prefix) = prefix {
+        if let Some(rest) = module.strip_prefix(prefix) {
+            rest.is_empty() || rest.starts_with("::")
+        } else {
+            false
+        }
+    } else {
+        false
+    }
+}
+
+impl Logger {
+    /// Log message on behalf of the logger, attributed to the main module of the executable.\
+    /// Unfiltered records are logged regardless of the log level and the record filter.
+    fn log_own(&self, level: Level, message: &str, unfiltered: bool) {
+        self.log_record(
+            &self.own_record(level, format_args!("{}", message)),
+            unfiltered,
+        );
+    }
+
+    /// A record logged on behalf of the logger, see log_own
+    fn own_record<'a>(&'a self, level: Level, args: fmt::Arguments<'a>) -> Record<'a> {
+        let target = self.exe_name.as_deref().unwrap_or("main");
+        Record::builder()
+            .level(level)
+            .target(target)
+            .module_path(Some(target))
+            .args(args)
+            .build()
+    }
+
+    /// Lock the logger on the exit paths, where waiting for the lock could deadlock.\
+    /// A poisoned lock is recovered, if the lock is held, eg. by the thread that panicked, None
+    /// is returned after a few attempts.
+    fn exit_lock(&self) -> Option<MutexGuard<'_, LoggerParams>> {
+        for attempt in 0..EXIT_LOCK_ATTEMPTS {
+            match self.inner.try_lock() {
+                Ok(guarded_params) => return Some(guarded_params),
+                Err(TryLockError::Poisoned(poisoned)) => return Some(poisoned.into_inner()),
+                Err(TryLockError::WouldBlock) if attempt + 1 < EXIT_LOCK_ATTEMPTS => {
+                    std::thread::sleep(Duration::from_millis(10));
                 }
+                Err(TryLockError::WouldBlock) => (),
+            }
+        }
+        None
+    }
+
+    // filter, format and write record, see Log::log
+    fn log_record(&self, record: &Record, unfiltered: bool) {
+        let mut guarded_params = self.inner.lock().unwrap();
+
+        // the observer runs without the lock, so it can use the logger
+        if let Some(observer) = guarded_params.record_observer() {
+            if guarded_params.in_active_window() && !IN_OBSERVER.with(Cell::get) {
+                drop(guarded_params);
+                let (mod_name, _mod_tag) = self.record_mod_names(record);
+                IN_OBSERVER.with(|in_observer| in_observer.set(true));
+                let observer = observer
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                observer(record.level(), &mod_name);
+                IN_OBSERVER.with(|in_observer| in_observer.set(false));
+                guarded_params = self.inner.lock().unwrap();
+            }
+        }
+
+        self.log_locked(&mut guarded_params, record, unfiltered);
+    }
+
+    /// The module names of record, see mod_names
+    fn record_mod_names(&self, record: &Record) -> (String, String) {
+        if let Some(mod_path) = record.module_path() {
+            self.mod_names(mod_path)
+        } else {
+            (String::from("undefined"), String::from("undefined"))
+        }
+    }
+
+    // filter, format and write record with the lock held, see log_record
+    fn log_locked(&self, guarded_params: &mut LoggerParams, record: &Record, unfiltered: bool) {
+        if !guarded_params.in_active_window() {
+            return;
+        }
+
+        let (mod_name, mod_tag) = self.record_mod_names(record);
+
+        let curr_level = record.metadata().level();
+
+        let mut level = guarded_params.default_level_filter();
+        if let Some(mod_level) = guarded_params.get_mod_level(&mod_tag) {
+            level = mod_level.to_level_filter();
+        }
+
+        if let Some(external_level) = guarded_params.external_level() {
+            let prefix = guarded_params
+                .internal_prefix()
+                .or(self.exe_name.as_deref());
+            if !is_internal(&mod_name, prefix) && level > external_level {
+                level = external_level.to_level_filter();
+            }
+        }
+
+        if let Some(thread_level) = THREAD_LEVELS.with(|levels| levels.borrow().last().copied()) {
+            level = thread_level.to_level_filter();
+        }
+
+        let audit = guarded_params.is_audit_target(record.target());
+
+        // audit records are not subject to level filtering or the record filter
+        let passes =
+            audit || unfiltered || (curr_level <= level && guarded_params.filter_record(record));
+        if passes
+            && !audit
+            && !unfiltered
+            && !guarded_params.callsite_permits(record.file(), record.line())
+        {
+            // the dropped record uses up its sequence number, so the gap reveals it
+            let _seq = guarded_params.next_seq();
+            self.suppression.record(Suppression::RateLimited, &mod_name);
+            return;
+        }
+        // records below the log level are kept for replay if enabled
+        let keep = !passes
+            && curr_level > level
+            && guarded_params.replay_enabled()
+            && guarded_params.filter_record(record);
+        if passes || keep {
+            let time = SystemTime::now();
+            let timestamp = if guarded_params.any_timestamp() {
+                let now: DateTime<FixedOffset> = if let Some(timezone) = guarded_params.timezone() {
+                    DateTime::<Local>::from(time).with_timezone(&timezone)
+                } else {
+                    DateTime::<Local>::from(time).into()
+                };
+                format_timestamp(
+                    guarded_params.timestamp_style(),
+                    now,
+                    guarded_params.uptime(),
+                    guarded_params.millis(),
+                )
             } else {
                 "".to_owned()
             };
 
-            let mut output = if guarded_params.brief_info() && (curr_level == Level::Info) {
-                format!(
-                    "{}{:<5} {}\n",
-                    timestamp,
-                    record.level().to_string(),
-                    record.args()
-                )
+            let adaptive = guarded_params.adaptive_format();
+            let brief = (adaptive || guarded_params.brief_info()) && (curr_level == Level::Info);
+            // adaptive format shows the source location of debug and trace messages
+            let show_location =
+                guarded_params.show_location() || (adaptive && curr_level >= Level::Debug);
+            let location = match (record.file(), record.line()) {
+                (Some(file), Some(line)) if show_location => format!("{}:{}: ", file, line),
+                _ => "".to_owned(),
+            };
+
+            let label = if audit {
+                AUDIT_LABEL.to_owned()
             } else {
-                format!(
-                    "{}{:<5} [{}] {}\n",
-                    timestamp,
-                    record.level().to_string(),
-                    &mod_name,
-                    record.args()
-                )
+                record.level().to_string()
             };
 
-            if guarded_params.color() {
-                output = match curr_level {
-                    Level::Error => format!("{}", output.red()),
-                    Level::Warn => format!("{}", output.yellow()),
-                    Level::Info => format!("{}", output.green()),
-                    Level::Debug => format!("{}", output.cyan()),
-                    Level::Trace => format!("{}", output.blue()),
+            // the target defaults to the module path, only show deliberately set targets
+            let target =
+                if guarded_params.show_target() && record.module_path() != Some(record.target()) {
+                    format!("({}) ", record.target())
+                } else {
+                    "".to_owned()
                 };
+
+            // records are numbered once they are written, see Line::set_seq
+            let level_head = level_head(None, &label);
+            let (open, close) = guarded_params.module_brackets();
+            let module_label = format!(
+                "{}{}{}",
+                open,
+                abbreviate_module(
+                    guarded_params.module_truncate().apply(&mod_name),
+                    guarded_params.module_max_len(),
+                ),
+                close
+            );
+            let module_head = if brief && !audit {
+                target
+            } else {
+                format!("{} {}{}", module_label, target, location)
+            };
+
+            let mut message = record.args().to_string();
+            if guarded_params.normalize_newlines() {
+                normalize_newlines(&mut message);
+            }
+            // redact before truncating, so secrets cannot be split by the truncation
+            guarded_params.redact(&mut message);
+            let max_message_len = guarded_params.max_message_len();
+            if max_message_len > 0 {
+                truncate_message(&mut message, max_message_len);
+            }
+            if let Some(breadcrumb) = context::breadcrumb() {
+                message = format!("{} {}", breadcrumb, message);
+            }
+            if let Some(since) = guarded_params.since_marker() {
+                message = format!("{} {}", message, since);
             }
 
-            let _res = match guarded_params.get_log_dest() {
-                LogDestination::Stderr => stderr().write(output.as_bytes()),
-                LogDestination::Stdout => stdout().write(output.as_bytes()),
-                LogDestination::Stream => {
-                    if let Some(ref mut stream) = guarded_params.log_stream() {
-                        stream.write(output.as_bytes())
-                    } else {
-                        stderr().write(output.as_bytes())
-                    }
-                }
-                LogDestination::StreamStdout => {
-                    if let Some(ref mut stream) = guarded_params.log_stream() {
-                        let _wres = stream.write(output.as_bytes());
-                    }
-                    stdout().write(output.as_bytes())
-                }
-                LogDestination::StreamStderr => {
-                    if let Some(ref mut stream) = guarded_params.log_stream() {
-                        let _wres = stream.write(output.as_bytes());
-                    }
-                    stderr().write(output.as_bytes())
-                }
-                LogDestination::Buffer => {
-                    if let Some(ref mut buffer) = guarded_params.log_buffer() {
-                        buffer.write(output.as_bytes())
-                    } else {
-                        stderr().write(output.as_bytes())
-                    }
-                }
-                LogDestination::BufferStdout => {
-                    if let Some(ref mut buffer) = guarded_params.log_buffer() {
-                        let _wres = buffer.write(output.as_bytes());
-                    }
-                    stdout().write(output.as_bytes())
-                }
-                LogDestination::BufferStderr => {
-                    if let Some(ref mut buffer) = guarded_params.log_buffer() {
-                        let _wres = buffer.write(output.as_bytes());
-                    }
-                    stderr().write(output.as_bytes())
-                }
+            let formatted = guarded_params.formatter().map(|formatter| {
+                formatter(
+                    record,
+                    &LogMeta {
+                        module: &mod_name,
+                        module_tag: &mod_tag,
+                        timestamp: timestamp.trim_end(),
+                        label: &label,
+                        audit,
+                        brief,
+                    },
+                )
+            });
+
+            let (color, tag_colors) = guarded_params.line_colors(curr_level, &mod_name);
+            let mut line = Line {
+                level: curr_level,
+                label,
+                color,
+                tag_colors,
+                module_label,
+                timestamp,
+                #[cfg(any(feature = "gelf", feature = "otlp"))]
+                time,
+                module: mod_name,
+                level_head,
+                module_head,
+                field_order: guarded_params.field_order(),
+                message,
+                format_fn: guarded_params.format_fn(),
+                formatted,
+                file: record.file().map(String::from),
+                line: record.line(),
+                seq: None,
+                json_fields: guarded_params.json_fields(),
+                columns: guarded_params.column_layout(),
             };
+
+            let opts = guarded_params.sink_opts();
+            // the timestamp is taken and all sinks are written while holding the lock, so records
+            // appear in the same order with monotonic timestamps in every sink
+            if keep {
+                guarded_params.keep_for_replay(line);
+            } else if audit {
+                line.set_seq(guarded_params.next_seq());
+                guarded_params.write_audit_line(&line, &opts);
+            } else {
+                // replayed records precede the record that triggered the replay
+                guarded_params.replay(curr_level, &opts);
+                line.set_seq(guarded_params.next_seq());
+                if guarded_params.write_mod_line(&mod_tag, &line, &opts) {
+                    guarded_params.write_line(&line, &opts);
+                }
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if self.timing.enabled() {
+            let started = Instant::now();
+            self.log_record(record, false);
+            self.timing.record(started.elapsed());
+        } else {
+            self.log_record(record, false);
         }
     }
 
@@ -492,6 +2529,31 @@ impl Log for Logger {
     }
 }
 
+/// Log a message only the first time it is logged with key during the lifetime of the process,
+/// attributed to the calling module.\
+/// Like Logger::log_once, but the message is formatted like in the macros of the log crate and
+/// module levels and module destinations of the calling module apply.
+///
+/// ```
+/// use mod_logger::{log_once, Level};
+/// for attempt in 0..3 {
+///     log_once!(Level::Warn, "retry", "retrying, attempt {}", attempt);
+/// }
+/// ```
+#[macro_export]
+macro_rules! log_once {
+    ($level:expr, $key:expr, $($arg:tt)+) => {
+        $crate::Logger::log_once_at(
+            $level,
+            $key,
+            module_path!(),
+            file!(),
+            line!(),
+            format_args!($($arg)+),
+        )
+    };
+}
+
 /*
 #[cfg(test)]
 mod test {
@@ -0,0 +1,157 @@
+use chrono::FixedOffset;
+use std::path::{Path, PathBuf};
+
+use crate::{LevelConfig, LogDestination, LogFormat, DEFAULT_LOG_DEST};
+
+/// A complete configuration of the logger applied in one step by Logger::apply.\
+/// Settings not given keep the defaults of the logger, so applying a LoggerConfig resets
+/// everything it covers.
+#[derive(Debug, Clone)]
+pub struct LoggerConfig {
+    levels: LevelConfig,
+    log_dest: LogDestination,
+    log_file: Option<PathBuf>,
+    format: LogFormat,
+    color: bool,
+    timestamp: bool,
+    millis: bool,
+    brief_info: bool,
+    timezone: Option<FixedOffset>,
+    wrap_width: Option<usize>,
+    max_message_len: usize,
+}
+
+impl<'a> LoggerConfig {
+    /// Create a LoggerConfig with the default settings of the logger
+    pub fn new() -> LoggerConfig {
+        LoggerConfig {
+            levels: LevelConfig::default(),
+            log_dest: DEFAULT_LOG_DEST,
+            log_file: None,
+            format: LogFormat::Text,
+            color: false,
+            timestamp: true,
+            millis: false,
+            brief_info: false,
+            timezone: None,
+            wrap_width: None,
+            max_message_len: 0,
+        }
+    }
+
+    /// Set the default and module log levels, module levels not given are removed
+    pub fn set_levels(&'a mut self, levels: LevelConfig) -> &'a mut LoggerConfig {
+        self.levels = levels;
+        self
+    }
+
+    /// Set the log destination, stream destinations require a log file that is opened in
+    /// append mode
+    pub fn set_log_dest(
+        &'a mut self,
+        log_dest: &LogDestination,
+        log_file: Option<&Path>,
+    ) -> &'a mut LoggerConfig {
+        self.log_dest = log_dest.clone();
+        self.log_file = log_file.map(|path| path.to_path_buf());
+        self
+    }
+
+    /// Set the output format
+    pub fn set_format(&'a mut self, format: LogFormat) -> &'a mut LoggerConfig {
+        self.format = format;
+        self
+    }
+
+    /// Enable / disable colored output
+    pub fn set_color(&'a mut self, color: bool) -> &'a mut LoggerConfig {
+        self.color = color;
+        self
+    }
+
+    /// Enable / disable timestamps in console messages
+    pub fn set_timestamp(&'a mut self, timestamp: bool) -> &'a mut LoggerConfig {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Enable / disable milliseconds in timestamps
+    pub fn set_millis(&'a mut self, millis: bool) -> &'a mut LoggerConfig {
+        self.millis = millis;
+        self
+    }
+
+    /// Enable / disable brief info messages
+    pub fn set_brief_info(&'a mut self, brief_info: bool) -> &'a mut LoggerConfig {
+        self.brief_info = brief_info;
+        self
+    }
+
+    /// Set the time zone of timestamps, ```None``` uses the local time zone
+    pub fn set_timezone(&'a mut self, timezone: Option<FixedOffset>) -> &'a mut LoggerConfig {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Set the console wrap width, see Logger::set_wrap_width
+    pub fn set_wrap_width(&'a mut self, wrap_width: Option<usize>) -> &'a mut LoggerConfig {
+        self.wrap_width = wrap_width;
+        self
+    }
+
+    /// Set the maximum message length, 0 disables truncation
+    pub fn set_max_message_len(&'a mut self, max_message_len: usize) -> &'a mut LoggerConfig {
+        self.max_message_len = max_message_len;
+        self
+    }
+
+    pub(crate) fn get_levels(&'a self) -> &'a LevelConfig {
+        &self.levels
+    }
+
+    pub(crate) fn get_log_dest(&'a self) -> &'a LogDestination {
+        &self.log_dest
+    }
+
+    pub(crate) fn get_log_file(&'a self) -> Option<&'a Path> {
+        self.log_file.as_deref()
+    }
+
+    pub(crate) fn get_format(&self) -> LogFormat {
+        self.format
+    }
+
+    pub(crate) fn get_color(&self) -> bool {
+        self.color
+    }
+
+    pub(crate) fn get_timestamp(&self) -> bool {
+        self.timestamp
+    }
+
+    pub(crate) fn get_millis(&self) -> bool {
+        self.millis
+    }
+
+    pub(crate) fn get_brief_info(&self) -> bool {
+        self.brief_info
+    }
+
+    pub(crate) fn get_timezone(&self) -> Option<FixedOffset> {
+        self.timezone
+    }
+
+    pub(crate) fn get_wrap_width(&self) -> Option<usize> {
+        self.wrap_width
+    }
+
+    pub(crate) fn get_max_message_len(&self) -> usize {
+        self.max_message_len
+    }
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,17 +1,55 @@
-use log::Level;
+use log::{Level, LevelFilter, Record};
 #[cfg(feature = "config")]
 use serde::Deserialize;
-use std::collections::HashMap;
-use std::io::{stderr, stdout, Write};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{stderr, stdout, BufWriter, LineWriter, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::result;
+use std::sync::atomic::{self, AtomicU64};
+#[cfg(windows)]
+use std::sync::OnceLock;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use super::{Error, ErrorKind, Result, DEFAULT_LOG_DEST};
+use super::async_writer::AsyncWriter;
+use super::buffer::LogBuffer;
+use super::format::{
+    ColumnLayout, Field, FieldNames, Formatter, Line, ModuleTrunc, RenderOpts, SinkOpts,
+    TimestampStyle, CSV_HEADER, DEFAULT_FIELD_ORDER,
+};
+#[cfg(feature = "gelf")]
+use super::gelf::GelfSender;
+use super::level_config::ALL_MODULES;
+#[cfg(feature = "otlp")]
+use super::otlp::OtlpSender;
+use super::pattern::{FormatFn, SharedFormatFn};
+use super::rotation::{rotate_files, rotate_timestamped, RotationConfig};
+use super::sink::LogSink;
+use super::stats::{LevelCounters, QueueCounters};
+use super::theme::{module_color, DEFAULT_LEVEL_COLORS, DEFAULT_MODULE_PALETTE};
+use super::{Error, ErrorKind, LevelConfig, LogFormat, Result, DEFAULT_LOG_DEST};
+use crate::error::ToError;
+use chrono::{DateTime, FixedOffset, Local};
+use colored::Color;
+use regex::Regex;
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::str::FromStr;
 
+// how often the log file is checked for rotation by another program
+const REOPEN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// how long size based rotation is suspended after it failed
+const ROTATION_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+// how often call sites that stopped logging are dropped by the call site rate limiter
+const CALLSITE_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "config")] {
         #[derive(Debug, Clone, PartialEq, Deserialize)]
+        #[non_exhaustive]
         pub enum LogDestination {
             /// log to stdout
             Stdout,
@@ -29,9 +67,22 @@ cfg_if::cfg_if! {
             BufferStdout,
             /// log to stderr and to a memory buffer
             BufferStderr,
+            /// send GELF messages to a Graylog server via UDP
+            #[cfg(feature = "gelf")]
+            Gelf {
+                /// the address of the server, eg. ```graylog:12201```
+                addr: String,
+            },
+            /// send OTLP log records to an OpenTelemetry collector via HTTP/protobuf
+            #[cfg(feature = "otlp")]
+            Otlp {
+                /// the logs endpoint of the collector, eg. ```http://collector:4318/v1/logs```
+                endpoint: String,
+            },
         }
     } else {
         #[derive(Debug, Clone, PartialEq)]
+        #[non_exhaustive]
         pub enum LogDestination {
             /// log to stdout
             Stdout,
@@ -49,6 +100,98 @@ cfg_if::cfg_if! {
             BufferStdout,
             /// log to stderr and to a memory buffer
             BufferStderr,
+            /// send GELF messages to a Graylog server via UDP
+            #[cfg(feature = "gelf")]
+            Gelf {
+                /// the address of the server, eg. ```graylog:12201```
+                addr: String,
+            },
+            /// send OTLP log records to an OpenTelemetry collector via HTTP/protobuf
+            #[cfg(feature = "otlp")]
+            Otlp {
+                /// the logs endpoint of the collector, eg. ```http://collector:4318/v1/logs```
+                endpoint: String,
+            },
+        }
+    }
+}
+
+/// The kinds of sinks the log destinations write to
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Sink {
+    /// stdout or stderr
+    Console,
+    /// the output file
+    Stream,
+    /// the memory buffer
+    Buffer,
+}
+
+/// How log files opened by the logger are buffered, see Logger::set_file_buffering
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BufferStrategy {
+    /// every line is written to the file right away
+    Unbuffered,
+    /// output is written to the file at the end of every line, suitable for ```tail -f```
+    LineBuffered,
+    /// output is written to the file in blocks of the given size in bytes
+    Block(usize),
+}
+
+impl BufferStrategy {
+    /// Wrap file in the writer implementing the strategy
+    pub(crate) fn wrap(&self, file: File) -> Box<dyn Write + Send> {
+        match self {
+            BufferStrategy::Unbuffered => Box::new(file),
+            BufferStrategy::LineBuffered => Box::new(LineWriter::new(file)),
+            BufferStrategy::Block(size) => Box::new(BufWriter::with_capacity(*size, file)),
+        }
+    }
+}
+
+/// The kinds of active destinations reported by Logger::list_destinations
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum DestinationKind {
+    /// stdout
+    Stdout,
+    /// stderr
+    Stderr,
+    /// a log file
+    File,
+    /// a stream given by the application
+    Stream,
+    /// the memory buffer
+    Buffer,
+    /// the stream receiving audit records
+    Audit,
+    /// a Graylog server receiving GELF messages
+    #[cfg(feature = "gelf")]
+    Gelf,
+    /// an OpenTelemetry collector receiving OTLP log records
+    #[cfg(feature = "otlp")]
+    Otlp,
+    /// a sink added with Logger::add_sink
+    Custom,
+}
+
+/// A description of an active destination
+#[derive(Debug, Clone, PartialEq)]
+pub struct DestinationInfo {
+    /// the kind of destination
+    pub kind: DestinationKind,
+    /// the target of the destination, eg. the path of a log file, if known
+    pub target: Option<String>,
+    /// the level threshold of the destination, None if it follows the levels of the logger
+    pub level: Option<Level>,
+}
+
+impl DestinationInfo {
+    fn new(kind: DestinationKind, target: Option<String>) -> DestinationInfo {
+        DestinationInfo {
+            kind,
+            target,
+            level: None,
         }
     }
 }
@@ -88,11 +231,28 @@ impl LogDestination {
             || self == &LogDestination::BufferStdout
             || self == &LogDestination::StreamStdout
     }
+
+    pub fn is_console(&self) -> bool {
+        self.is_stdout() || self.is_stderr()
+    }
 }
 
 impl FromStr for LogDestination {
     type Err = Error;
     fn from_str(dest: &str) -> result::Result<Self, Self::Err> {
+        #[cfg(feature = "otlp")]
+        if let Some(endpoint) = dest.strip_prefix("otlp:") {
+            return Ok(LogDestination::Otlp {
+                endpoint: endpoint.to_owned(),
+            });
+        }
+        #[cfg(feature = "gelf")]
+        if let Some(addr) = dest.strip_prefix("gelf:") {
+            return Ok(LogDestination::Gelf {
+                addr: addr.to_owned(),
+            });
+        }
+
         if let Some(pos) = DEST_TX
             .iter()
             .position(|val| val.0.eq_ignore_ascii_case(dest))
@@ -107,17 +267,193 @@ impl FromStr for LogDestination {
     }
 }
 
+/// The destination of a module set with Logger::set_mod_dest
+enum ModSink {
+    Stdout,
+    Stderr,
+    Stream(Box<dyn Write + Send>),
+}
+
+impl ModSink {
+    fn write_all(&mut self, output: &[u8]) -> std::io::Result<()> {
+        match self {
+            ModSink::Stdout => stdout().lock().write_all(output),
+            ModSink::Stderr => stderr().lock().write_all(output),
+            ModSink::Stream(stream) => stream.write_all(output),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            ModSink::Stdout => stdout().flush(),
+            ModSink::Stderr => stderr().flush(),
+            ModSink::Stream(stream) => stream.flush(),
+        }
+    }
+}
+
+struct ModDest {
+    sink: ModSink,
+    // also write records of the module to the log destination
+    also_global: bool,
+}
+
+/// The token bucket of a call site, see Logger::set_callsite_rate_limit
+struct TokenBucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_sec: u32) -> TokenBucket {
+        TokenBucket {
+            tokens: per_sec as f64,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Refill the bucket and take a token, returns false if the bucket is empty
+    fn take(&mut self, per_sec: u32) -> bool {
+        let now = Instant::now();
+        let refill = now.duration_since(self.refilled).as_secs_f64() * per_sec as f64;
+        self.tokens = (self.tokens + refill).min(per_sec as f64);
+        self.refilled = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Find the entry for module in map, walking up the module path.\
+/// Returns the module path the entry was configured for along with the entry.
+fn find_mod_entry<'b, 'c, T>(
+    map: &'c HashMap<String, T>,
+    module: &'b str,
+) -> Option<(&'b str, &'c T)> {
+    let mut mod_path = module;
+
+    loop {
+        if let Some(entry) = map.get(mod_path) {
+            return Some((mod_path, entry));
+        }
+        if let Some(index) = mod_path.rfind("::") {
+            let (mod_new, _dumm) = mod_path.split_at(index);
+            mod_path = mod_new;
+        } else {
+            return None;
+        }
+    }
+}
+
+/// A function observing level and module of every record, see Logger::set_record_observer
+pub type RecordObserver = Box<dyn Fn(Level, &str) + Send>;
+
+/// A function deciding whether a record is logged, see Logger::set_filter
+pub type RecordFilter = Box<dyn Fn(&Record) -> bool + Send>;
+
+/// A function receiving every rendered line, see Logger::set_tap
+pub type LineTap = Box<dyn Fn(&str) + Send>;
+
 pub(crate) struct LoggerParams {
     log_dest: LogDestination,
     log_stream: Option<Box<dyn Write + Send>>,
-    log_buffer: Option<Vec<u8>>,
+    log_path: Option<PathBuf>,
+    rotation: Option<RotationConfig>,
+    // when size based rotation last failed, it is not retried before ROTATION_RETRY_INTERVAL
+    rotation_failed: Option<Instant>,
+    // the size of the log file when it was opened, rotation applies to it plus the bytes written
+    opened_bytes: u64,
+    // output written to the log stream since it was opened
+    written: (u64, u64),
+    lazy_open: bool,
+    reopen_on_rotate: bool,
+    // identity of the log file and when it was last checked, see check_reopen
+    file_id: Option<FileId>,
+    file_checked: Instant,
+    // records per second and call site, 0 disables the call site rate limiter
+    callsite_rate: u32,
+    // token buckets by source file and line, cleaned up every CALLSITE_CLEANUP_INTERVAL
+    callsite_buckets: HashMap<String, HashMap<u32, TokenBucket>>,
+    callsite_cleaned: Instant,
+    #[cfg(feature = "gelf")]
+    gelf: Option<GelfSender>,
+    #[cfg(feature = "otlp")]
+    otlp: Option<OtlpSender>,
+    hostname: String,
+    // named points in time, see Logger::mark
+    markers: HashMap<String, Instant>,
+    show_since: Option<String>,
+    active_window: (Option<Instant>, Option<Instant>),
+    redactions: Vec<(Regex, String)>,
+    log_buffer: Option<LogBuffer>,
+    // the buffer is fed in addition to the log file, see Logger::set_buffer_and_file
+    buffer_with_file: bool,
+    // the file the buffer is written to at exit, see Logger::set_buffer_dump_on_exit
+    buffer_dump: Option<PathBuf>,
     default_level: Level,
+    default_off: bool,
+    // the default level was set from the environment when the logger was created
+    env_default_level: bool,
     mod_level: HashMap<String, Level>,
     max_level: Level,
+    external_level: Option<Level>,
+    internal_prefix: Option<String>,
+    module_brackets: (String, String),
+    show_target: bool,
+    level_colors: [Color; 5],
+    color_by_level: bool,
+    color_by_module: bool,
+    module_palette: Vec<Color>,
+    banner: Option<String>,
+    banner_header: bool,
+    once_keys: HashSet<String>,
+    record_observer: Option<Arc<Mutex<RecordObserver>>>,
+    record_filter: Option<RecordFilter>,
+    line_tap: Option<LineTap>,
+    replay: Option<(Level, usize)>,
+    level_counters: LevelCounters,
+    summary_on_flush: bool,
+    replay_ring: VecDeque<Line>,
+    strict_modules: bool,
+    known_modules: HashSet<String>,
+    run_separator: Option<String>,
+    async_capacity: Option<usize>,
+    file_buffering: BufferStrategy,
+    queue_counters: Arc<QueueCounters>,
+    audit_target: Option<String>,
+    audit_stream: Option<Box<dyn Write + Send>>,
+    mod_dest: HashMap<String, ModDest>,
+    sinks: Vec<Box<dyn LogSink>>,
+    // output was written to a stream or sink since the last flush
+    unflushed: bool,
+    wrap_width: Option<usize>,
+    format: LogFormat,
+    format_fn: Option<SharedFormatFn>,
+    formatter: Option<Formatter>,
+    json_fields: Arc<FieldNames>,
+    field_order: Arc<[Field]>,
+    columns: Option<Arc<ColumnLayout>>,
+    adaptive_format: bool,
+    indent_multiline: bool,
+    normalize_newlines: bool,
+    module_trunc: ModuleTrunc,
+    show_location: bool,
+    timestamp_style: TimestampStyle,
+    // the reference of TimestampStyle::Uptime
+    created: Instant,
+    max_message_len: usize,
+    module_max_len: usize,
+    show_seq: bool,
+    seq: AtomicU64,
     color: bool,
     brief_info: bool,
     timestamp: bool,
+    sink_timestamp: [Option<bool>; 3],
     millis: bool,
+    timezone: Option<FixedOffset>,
     initialised: bool,
 }
 
@@ -126,15 +462,89 @@ impl<'a> LoggerParams {
         LoggerParams {
             log_dest: DEFAULT_LOG_DEST,
             log_stream: None,
+            log_path: None,
+            rotation: None,
+            rotation_failed: None,
+            opened_bytes: 0,
+            written: (0, 0),
+            lazy_open: false,
+            reopen_on_rotate: false,
+            file_id: None,
+            file_checked: Instant::now(),
+            callsite_rate: 0,
+            callsite_buckets: HashMap::new(),
+            callsite_cleaned: Instant::now(),
+            #[cfg(feature = "gelf")]
+            gelf: None,
+            #[cfg(feature = "otlp")]
+            otlp: None,
+            hostname: detect_hostname(),
+            markers: HashMap::new(),
+            show_since: None,
+            active_window: (None, None),
+            redactions: Vec::new(),
             log_buffer: None,
+            buffer_with_file: false,
+            buffer_dump: None,
             default_level: log_level,
+            default_off: false,
+            env_default_level: false,
             max_level: log_level,
             mod_level: HashMap::new(),
+            external_level: None,
+            internal_prefix: None,
+            module_brackets: (String::from("["), String::from("]")),
+            show_target: false,
+            level_colors: DEFAULT_LEVEL_COLORS,
+            color_by_level: true,
+            color_by_module: false,
+            module_palette: DEFAULT_MODULE_PALETTE.to_vec(),
+            banner: None,
+            banner_header: false,
+            once_keys: HashSet::new(),
+            record_observer: None,
+            record_filter: None,
+            line_tap: None,
+            replay: None,
+            level_counters: LevelCounters::new(),
+            summary_on_flush: false,
+            replay_ring: VecDeque::new(),
+            strict_modules: false,
+            known_modules: HashSet::new(),
+            run_separator: None,
+            async_capacity: None,
+            file_buffering: BufferStrategy::Unbuffered,
+            queue_counters: Arc::new(QueueCounters::new()),
+            audit_target: None,
+            audit_stream: None,
+            mod_dest: HashMap::new(),
+            sinks: Vec::new(),
+            unflushed: false,
+            wrap_width: None,
+            format: LogFormat::Text,
+            format_fn: None,
+            formatter: None,
+            json_fields: Arc::new(FieldNames::new()),
+            field_order: Arc::new(DEFAULT_FIELD_ORDER),
+            columns: None,
+            adaptive_format: false,
+            indent_multiline: false,
+            normalize_newlines: false,
+            module_trunc: ModuleTrunc::Full,
+            show_location: false,
+            timestamp_style: TimestampStyle::DateTime,
+            created: Instant::now(),
+            max_message_len: 0,
+            module_max_len: 0,
+            show_seq: false,
+            seq: AtomicU64::new(0),
             initialised: false,
             color: false,
             brief_info: false,
             timestamp: true,
+            sink_timestamp: [None; 3],
             millis: false,
+            timezone: None,
         }
     }
 
@@ -149,7 +559,11 @@ impl<'a> LoggerParams {
 
     fn recalculate_max_level(&mut self) {
         // TODO: implement
-        let mut max_level = self.default_level;
+        let mut max_level = if self.default_off {
+            Level::Error
+        } else {
+            self.default_level
+        };
         for level in self.mod_level.values() {
             if max_level < *level {
                 max_level = *level;
@@ -163,163 +577,1889 @@ impl<'a> LoggerParams {
     }
 
     pub fn get_mod_level(&'a self, module: &str) -> Option<Level> {
-        let mut mod_path = module;
+        self.find_mod_level(module).map(|(_, level)| level)
+    }
+
+    /// The level set for exactly module, the default level for ```*```
+    pub fn exact_mod_level(&'a self, module: &str) -> Option<LevelFilter> {
+        if module == ALL_MODULES {
+            Some(self.default_level_filter())
+        } else {
+            self.mod_level.get(module).map(Level::to_level_filter)
+        }
+    }
 
-        loop {
-            if let Some(level) = self.mod_level.get(mod_path) {
-                return Some(*level);
+    /// Restore the level of module to level as returned by exact_mod_level, None removes it
+    pub fn restore_mod_level(&'a mut self, module: &str, level: Option<LevelFilter>) -> &'a Level {
+        if module == ALL_MODULES {
+            if let Some(level) = level {
+                self.set_default_level_filter(level);
             }
-            if let Some(index) = mod_path.rfind("::") {
-                let (mod_new, _dumm) = mod_path.split_at(index);
-                mod_path = mod_new;
-            } else {
-                return None;
+        } else {
+            match level.and_then(|level| level.to_level()) {
+                Some(level) => {
+                    self.mod_level.insert(String::from(module), level);
+                }
+                None => {
+                    self.mod_level.remove(module);
+                }
             }
+            self.recalculate_max_level();
         }
+        &self.max_level
     }
 
-    pub fn set_color(&'a mut self, color: bool) {
-        self.color = color;
+    /// Find the module level for module, walking up the module path.\
+    /// Returns the module path the level was configured for along with the level.
+    pub fn find_mod_level<'b>(&'a self, module: &'b str) -> Option<(&'b str, Level)> {
+        find_mod_entry(&self.mod_level, module).map(|(mod_path, level)| (mod_path, *level))
     }
 
-    pub fn color(&'a mut self) -> bool {
-        self.color
+    pub fn mark(&'a mut self, name: &str) {
+        self.markers.insert(String::from(name), Instant::now());
     }
 
-    pub fn set_brief_info(&'a mut self, val: bool) {
-        self.brief_info = val;
+    pub fn set_show_since(&'a mut self, name: Option<&str>) {
+        self.show_since = name.map(String::from);
     }
-    pub fn brief_info(&'a mut self) -> bool {
-        self.brief_info
+
+    /// The time elapsed since the marker shown in records, eg. ```(+1.234s since startup)```.\
+    /// None if no marker is shown or it has not been set yet.
+    pub fn since_marker(&'a self) -> Option<String> {
+        let name = self.show_since.as_ref()?;
+        let marked = self.markers.get(name)?;
+        Some(format!(
+            "(+{:.3}s since {})",
+            marked.elapsed().as_secs_f64(),
+            name
+        ))
     }
 
-    pub fn set_timestamp(&'a mut self, val: bool) {
-        self.timestamp = val;
+    pub fn set_external_level(&'a mut self, level: Option<Level>) {
+        self.external_level = level;
     }
-    pub fn timestamp(&'a mut self) -> bool {
-        self.timestamp
+
+    pub fn external_level(&'a self) -> Option<Level> {
+        self.external_level
     }
 
-    pub fn set_millis(&'a mut self, val: bool) {
-        self.millis = val;
+    pub fn set_internal_prefix(&'a mut self, prefix: Option<&str>) {
+        self.internal_prefix = prefix.map(String::from);
     }
-    pub fn millis(&'a mut self) -> bool {
-        self.millis
+
+    pub fn internal_prefix(&'a self) -> Option<&'a str> {
+        self.internal_prefix.as_deref()
     }
 
-    pub fn set_mod_level(&'a mut self, module: &str, level: Level) -> &'a Level {
-        self.mod_level.insert(String::from(module), level);
-        match level.cmp(&self.max_level) {
-            Ordering::Greater => {
-                self.max_level = level;
-            }
-            Ordering::Less => {
-                self.recalculate_max_level();
-            }
-            _ => (),
-        };
-        &self.max_level
+    pub fn set_module_brackets(&'a mut self, open: &str, close: &str) {
+        self.module_brackets = (String::from(open), String::from(close));
     }
 
-    #[cfg(feature = "config")]
-    pub fn set_mod_config(&'a mut self, mod_config: &HashMap<String, Level>) -> &'a Level {
-        for module in mod_config.keys() {
-            if let Some(level) = mod_config.get(module) {
-                self.mod_level.insert(module.clone(), *level);
-            }
-        }
-        self.recalculate_max_level();
-        &self.max_level
+    pub fn module_brackets(&'a self) -> (&'a str, &'a str) {
+        (&self.module_brackets.0, &self.module_brackets.1)
     }
 
-    pub fn set_default_level(&'a mut self, level: Level) -> Level {
-        self.default_level = level;
-        if level >= self.max_level {
-            self.max_level = level;
+    pub fn set_show_target(&'a mut self, val: bool) {
+        self.show_target = val;
+    }
+
+    pub fn show_target(&'a self) -> bool {
+        self.show_target
+    }
+
+    pub fn set_banner(&'a mut self, banner: &str) {
+        self.banner = Some(String::from(banner));
+    }
+
+    pub fn set_banner_header(&'a mut self, val: bool) {
+        self.banner_header = val;
+    }
+
+    pub fn set_wrap_width(&'a mut self, width: Option<usize>) {
+        self.wrap_width = width;
+    }
+
+    pub fn wrap_width(&'a self) -> Option<usize> {
+        self.wrap_width
+    }
+
+    /// Fail if output in format can't be written to dest, binary output can't go to the console
+    pub fn check_format(format: LogFormat, dest: &LogDestination) -> Result<()> {
+        if format.is_binary() && dest.is_console() {
+            Err(Error::with_context(
+                ErrorKind::InvParam,
+                &format!(
+                    "{:?} output can not be written to the console of log destination {:?}",
+                    format, dest
+                ),
+            ))
         } else {
-            self.recalculate_max_level()
+            Ok(())
         }
-        self.max_level
     }
 
-    pub fn get_default_level(&'a self) -> Level {
-        self.default_level
+    pub fn set_format(&'a mut self, format: LogFormat) {
+        if format == LogFormat::Csv && self.format != LogFormat::Csv {
+            self.write_csv_header();
+        }
+        self.format = format;
     }
 
-    pub fn get_log_dest(&'a self) -> &'a LogDestination {
-        &self.log_dest
+    pub fn format(&'a self) -> LogFormat {
+        self.format
     }
 
-    pub fn log_stream(&mut self) -> &mut Option<Box<dyn 'static + Write + Send>> {
-        &mut self.log_stream
+    fn write_csv_header(&mut self) {
+        if self.log_dest.is_stream_dest() {
+            if let Some(ref mut stream) = self.log_stream {
+                let _res = stream.write_all(CSV_HEADER.as_bytes());
+            }
+        } else if self.log_dest.is_buffer_dest() {
+            if let Some(ref mut buffer) = self.log_buffer {
+                let _res = buffer.write_all(CSV_HEADER.as_bytes());
+            }
+        }
+
+        if self.log_dest.is_stderr() {
+            let _res = stderr().write_all(CSV_HEADER.as_bytes());
+        } else if self.log_dest.is_stdout() {
+            let _res = stdout().write_all(CSV_HEADER.as_bytes());
+        }
     }
 
-    pub fn log_buffer(&mut self) -> Option<&mut Vec<u8>> {
-        if let Some(ref mut buffer) = self.log_buffer {
-            Some(buffer)
+    pub fn set_adaptive_format(&'a mut self, val: bool) {
+        self.adaptive_format = val;
+    }
+
+    pub fn adaptive_format(&'a self) -> bool {
+        self.adaptive_format
+    }
+
+    pub fn set_show_location(&'a mut self, val: bool) {
+        self.show_location = val;
+    }
+
+    pub fn show_location(&'a self) -> bool {
+        self.show_location
+    }
+
+    pub fn set_indent_multiline(&'a mut self, val: bool) {
+        self.indent_multiline = val;
+    }
+
+    pub fn indent_multiline(&'a self) -> bool {
+        self.indent_multiline
+    }
+
+    pub fn set_module_truncate(&'a mut self, module_trunc: ModuleTrunc) {
+        self.module_trunc = module_trunc;
+    }
+
+    pub fn module_truncate(&'a self) -> ModuleTrunc {
+        self.module_trunc
+    }
+
+    pub fn set_normalize_newlines(&'a mut self, val: bool) {
+        self.normalize_newlines = val;
+    }
+
+    pub fn normalize_newlines(&'a self) -> bool {
+        self.normalize_newlines
+    }
+
+    pub fn set_max_message_len(&'a mut self, max_len: usize) {
+        self.max_message_len = max_len;
+    }
+
+    pub fn max_message_len(&'a self) -> usize {
+        self.max_message_len
+    }
+
+    pub fn set_module_max_len(&'a mut self, max_len: usize) {
+        self.module_max_len = max_len;
+    }
+
+    pub fn module_max_len(&'a self) -> usize {
+        self.module_max_len
+    }
+
+    pub fn set_show_seq(&'a mut self, val: bool) {
+        self.show_seq = val;
+    }
+
+    /// The sequence number of the next emitted record, if enabled
+    pub fn next_seq(&'a self) -> Option<u64> {
+        if self.show_seq {
+            Some(self.seq.fetch_add(1, atomic::Ordering::Relaxed) + 1)
         } else {
             None
         }
     }
 
-    pub fn retrieve_log_buffer(&mut self) -> Option<Vec<u8>> {
-        if let Some(ref mut buffer) = self.log_buffer {
-            let tmp = buffer.clone();
-            buffer.clear();
-            Some(tmp)
+    /// Returns true if key has not been seen before
+    pub fn mark_once(&'a mut self, key: &str) -> bool {
+        if self.once_keys.contains(key) {
+            false
         } else {
-            None
+            self.once_keys.insert(String::from(key))
         }
     }
 
-    pub fn flush(&mut self) {
-        if self.log_dest.is_stream_dest() {
-            if let Some(ref mut stream) = self.log_stream() {
-                let _res = stream.flush();
+    pub fn reset_once(&'a mut self) {
+        self.once_keys.clear();
+    }
+
+    pub fn set_record_observer(&'a mut self, observer: Option<RecordObserver>) {
+        self.record_observer = observer.map(|observer| Arc::new(Mutex::new(observer)));
+    }
+
+    pub fn record_observer(&'a self) -> Option<Arc<Mutex<RecordObserver>>> {
+        self.record_observer.clone()
+    }
+
+    pub fn set_record_filter(&'a mut self, filter: Option<RecordFilter>) {
+        self.record_filter = filter;
+    }
+
+    pub fn set_line_tap(&'a mut self, tap: Option<LineTap>) {
+        self.line_tap = tap;
+    }
+
+    pub fn set_replay(&'a mut self, replay: Option<(Level, usize)>) {
+        self.replay = replay.filter(|(_, context_lines)| *context_lines > 0);
+        self.replay_ring.clear();
+    }
+
+    pub fn level_counters(&'a self) -> &'a LevelCounters {
+        &self.level_counters
+    }
+
+    pub fn set_summary_on_flush(&'a mut self, val: bool) {
+        self.summary_on_flush = val;
+    }
+
+    pub fn summary_on_flush(&'a self) -> bool {
+        self.summary_on_flush
+    }
+
+    /// Returns true if records below the log level are kept for replay
+    pub fn replay_enabled(&'a self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Keep a line below the log level for replay, dropping the oldest line if the ring is full
+    pub fn keep_for_replay(&mut self, line: Line) {
+        if let Some((_, context_lines)) = self.replay {
+            if self.replay_ring.len() >= context_lines {
+                self.replay_ring.pop_front();
             }
+            self.replay_ring.push_back(line);
         }
+    }
 
-        if self.log_dest.is_stderr() {
-            let _res = stderr().flush();
-        } else if self.log_dest.is_stdout() {
-            let _res = stdout().flush();
+    /// Write the kept lines if level triggers the replay
+    pub fn replay(&mut self, level: Level, opts: &SinkOpts) {
+        match self.replay {
+            Some((trigger, _)) if level <= trigger => {
+                for mut line in mem::take(&mut self.replay_ring) {
+                    line.set_seq(self.next_seq());
+                    self.write_line(&line, opts);
+                }
+            }
+            _ => (),
         }
     }
 
-    pub fn set_log_dest<S: 'static + Write + Send>(
-        &mut self,
-        dest: &LogDestination,
-        stream: Option<S>,
-    ) -> Result<()> {
-        // TODO: flush ?
+    /// Check if record passes the record filter, true if no filter is set
+    pub fn filter_record(&'a self, record: &Record) -> bool {
+        self.record_filter
+            .as_ref()
+            .is_none_or(|filter| filter(record))
+    }
 
-        self.flush();
+    pub fn set_format_fn(&'a mut self, format_fn: Option<FormatFn>) {
+        self.format_fn = format_fn.map(Arc::from);
+    }
 
-        if dest.is_stream_dest() {
-            if let Some(stream) = stream {
-                self.log_dest = dest.clone();
-                self.log_stream = Some(Box::new(stream));
-                Ok(())
-            } else {
-                Err(Error::with_context(
+    pub fn format_fn(&'a self) -> Option<SharedFormatFn> {
+        self.format_fn.clone()
+    }
+
+    pub fn set_formatter(&'a mut self, formatter: Option<Formatter>) {
+        self.formatter = formatter;
+    }
+
+    pub fn formatter(&'a self) -> Option<&'a Formatter> {
+        self.formatter.as_ref()
+    }
+
+    pub fn set_json_fields(&'a mut self, fields: FieldNames) -> Result<()> {
+        let keys = fields.keys();
+        for (index, key) in keys.iter().enumerate() {
+            if keys[..index].contains(key) {
+                return Err(Error::with_context(
                     ErrorKind::InvParam,
-                    &format!("no stream given for log destination type {:?}", dest),
-                ))
-            }
-        } else if dest.is_buffer_dest() {
-            self.log_dest = dest.clone();
-            self.log_stream = None;
-            if self.log_buffer.is_none() {
-                self.log_buffer = Some(Vec::new());
+                    &format!("JSON field name '{}' is used more than once", key),
+                ));
             }
-            Ok(())
+        }
+        self.json_fields = Arc::new(fields);
+        Ok(())
+    }
+
+    pub fn json_fields(&'a self) -> Arc<FieldNames> {
+        self.json_fields.clone()
+    }
+
+    pub fn set_callsite_rate_limit(&'a mut self, per_sec: u32) {
+        self.callsite_rate = per_sec;
+        self.callsite_buckets.clear();
+    }
+
+    /// Check the call site rate limit for a record from file and line.\
+    /// Returns false if the call site exceeded its rate, records without source location are
+    /// not limited.
+    pub fn callsite_permits(&'a mut self, file: Option<&str>, line: Option<u32>) -> bool {
+        let per_sec = self.callsite_rate;
+        let (file, line) = match (file, line) {
+            (Some(file), Some(line)) if per_sec > 0 => (file, line),
+            _ => return true,
+        };
+
+        if self.callsite_cleaned.elapsed() >= CALLSITE_CLEANUP_INTERVAL {
+            // buckets idle for a full interval are full again and can be dropped
+            self.callsite_buckets.retain(|_file, lines| {
+                lines.retain(|_line, bucket| bucket.refilled.elapsed() < CALLSITE_CLEANUP_INTERVAL);
+                !lines.is_empty()
+            });
+            self.callsite_cleaned = Instant::now();
+        }
+
+        if let Some(lines) = self.callsite_buckets.get_mut(file) {
+            lines
+                .entry(line)
+                .or_insert_with(|| TokenBucket::new(per_sec))
+                .take(per_sec)
         } else {
-            self.log_stream = None;
-            self.log_dest = dest.clone();
-            if self.log_buffer.is_some() {
-                self.log_buffer = None;
+            let mut bucket = TokenBucket::new(per_sec);
+            let permitted = bucket.take(per_sec);
+            self.callsite_buckets
+                .insert(String::from(file), HashMap::from([(line, bucket)]));
+            permitted
+        }
+    }
+
+    pub fn set_column_layout(&'a mut self, columns: Option<ColumnLayout>) -> Result<()> {
+        if let Some(ref columns) = columns {
+            for field in DEFAULT_FIELD_ORDER {
+                if let Some((0, _)) = columns.column(field) {
+                    return Err(Error::with_context(
+                        ErrorKind::InvParam,
+                        &format!("column width of field {:?} must not be 0", field),
+                    ));
+                }
+            }
+        }
+        self.columns = columns.map(Arc::new);
+        Ok(())
+    }
+
+    pub fn column_layout(&'a self) -> Option<Arc<ColumnLayout>> {
+        self.columns.clone()
+    }
+
+    pub fn set_field_order(&'a mut self, fields: &[Field]) -> Result<()> {
+        if fields.is_empty() {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                "the field order must contain at least one field",
+            ));
+        }
+        for (index, field) in fields.iter().enumerate() {
+            if fields[..index].contains(field) {
+                return Err(Error::with_context(
+                    ErrorKind::InvParam,
+                    &format!("field {:?} is used more than once", field),
+                ));
+            }
+        }
+        self.field_order = Arc::from(fields);
+        Ok(())
+    }
+
+    pub fn field_order(&'a self) -> Arc<[Field]> {
+        self.field_order.clone()
+    }
+
+    pub fn add_redaction(&'a mut self, pattern: Regex, replacement: &str) {
+        self.redactions.push((pattern, String::from(replacement)));
+    }
+
+    pub fn clear_redactions(&'a mut self) {
+        self.redactions.clear();
+    }
+
+    /// Apply all redactions to message in the order they were added
+    pub fn redact(&'a self, message: &mut String) {
+        for (pattern, replacement) in &self.redactions {
+            if let Cow::Owned(redacted) = pattern.replace_all(message, replacement.as_str()) {
+                *message = redacted;
+            }
+        }
+    }
+
+    pub fn set_active_window(&'a mut self, start: Option<Instant>, stop: Option<Instant>) {
+        self.active_window = (start, stop);
+    }
+
+    pub fn in_active_window(&'a self) -> bool {
+        match self.active_window {
+            (None, None) => true,
+            (start, stop) => {
+                let now = Instant::now();
+                start.is_none_or(|start| now >= start) && stop.is_none_or(|stop| now <= stop)
+            }
+        }
+    }
+
+    pub fn set_hostname(&'a mut self, hostname: &str) {
+        self.hostname = String::from(hostname);
+        #[cfg(feature = "otlp")]
+        if let Some(ref otlp) = self.otlp {
+            otlp.set_host(hostname);
+        }
+    }
+
+    pub fn set_audit_target(&'a mut self, target: Option<&str>) {
+        self.audit_target = target.map(String::from);
+    }
+
+    pub fn is_audit_target(&'a self, target: &str) -> bool {
+        self.audit_target.as_deref() == Some(target)
+    }
+
+    pub fn set_audit_stream<S: 'static + Write + Send>(&'a mut self, stream: Option<S>) {
+        if let Some(ref mut stream) = self.audit_stream {
+            let _res = stream.flush();
+        }
+        self.audit_stream = stream.map(|stream| Box::new(stream) as Box<dyn Write + Send>);
+    }
+
+    /// Returns true if records must reach the logger regardless of the max level
+    pub fn needs_all_records(&'a self) -> bool {
+        self.record_observer.is_some() || self.audit_target.is_some() || self.replay.is_some()
+    }
+
+    pub fn set_color(&'a mut self, color: bool) {
+        self.color = color && (!self.log_dest.is_console() || enable_virtual_terminal());
+    }
+
+    pub fn set_level_color(&'a mut self, level: Level, color: Color) {
+        self.level_colors[level as usize - 1] = color;
+    }
+
+    pub fn set_level_colors(&'a mut self, colors: [Color; 5]) {
+        self.level_colors = colors;
+    }
+
+    pub fn level_color(&'a self, level: Level) -> Color {
+        self.level_colors[level as usize - 1]
+    }
+
+    pub fn set_color_by_level(&'a mut self, val: bool) {
+        self.color_by_level = val;
+    }
+
+    pub fn set_color_by_module(&'a mut self, val: bool) {
+        self.color_by_module = val;
+    }
+
+    pub fn set_module_palette(&'a mut self, palette: &[Color]) -> Result<()> {
+        if palette.is_empty() {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                "the module palette must not be empty",
+            ));
+        }
+        self.module_palette = palette.to_vec();
+        Ok(())
+    }
+
+    /// The color of the whole line and the colors of level and module tag of a record, see
+    /// Line::color and Line::tag_colors
+    pub fn line_colors(
+        &'a self,
+        level: Level,
+        module: &str,
+    ) -> (Option<Color>, Option<(Color, Color)>) {
+        let level_color = self.level_color(level);
+        match (self.color_by_level, self.color_by_module) {
+            (true, false) => (Some(level_color), None),
+            (false, true) => (Some(module_color(&self.module_palette, module)), None),
+            (true, true) => (
+                None,
+                Some((level_color, module_color(&self.module_palette, module))),
+            ),
+            (false, false) => (None, None),
+        }
+    }
+
+    pub fn color(&'a self) -> bool {
+        self.color
+    }
+
+    /// The color of lines written by Logger::write_raw, they have no module to color by
+    pub fn raw_line_color(&'a self, level: Level) -> Option<Color> {
+        if self.color_by_level {
+            Some(self.level_color(level))
+        } else {
+            None
+        }
+    }
+
+    /// The rendering options of all sinks
+    pub fn sink_opts(&'a self) -> SinkOpts {
+        let sink_opts = |sink: Sink, wrap_width: Option<usize>| RenderOpts {
+            format: self.format(),
+            timestamp: self.include_timestamp(sink),
+            wrap_width,
+            multiline: self.indent_multiline(),
+            color: self.color,
+        };
+
+        SinkOpts {
+            // console destinations may receive a wrapped variant of the output
+            console: sink_opts(Sink::Console, self.wrap_width()),
+            stream: sink_opts(Sink::Stream, None),
+            // the buffer always receives plain output, the escape sequences would only get in
+            // the way when the buffer is retrieved
+            buffer: RenderOpts {
+                color: false,
+                ..sink_opts(Sink::Buffer, None)
+            },
+        }
+    }
+
+    pub fn set_brief_info(&'a mut self, val: bool) {
+        self.brief_info = val;
+    }
+    pub fn brief_info(&'a self) -> bool {
+        self.brief_info
+    }
+
+    pub fn set_timestamp(&'a mut self, val: bool) {
+        self.timestamp = val;
+    }
+
+    pub fn timestamp(&'a self) -> bool {
+        self.timestamp
+    }
+
+    pub fn set_sink_timestamp(&'a mut self, sink: Sink, val: Option<bool>) {
+        self.sink_timestamp[sink as usize] = val;
+    }
+
+    pub fn include_timestamp(&'a self, sink: Sink) -> bool {
+        self.sink_timestamp[sink as usize].unwrap_or(match sink {
+            Sink::Console => self.timestamp,
+            // file and buffer records are archived, keep them self-describing
+            Sink::Stream | Sink::Buffer => true,
+        })
+    }
+
+    pub fn any_timestamp(&'a self) -> bool {
+        self.include_timestamp(Sink::Console)
+            || self.include_timestamp(Sink::Stream)
+            || self.include_timestamp(Sink::Buffer)
+    }
+
+    pub fn set_millis(&'a mut self, val: bool) {
+        self.millis = val;
+    }
+    pub fn millis(&'a self) -> bool {
+        self.millis
+    }
+
+    pub fn set_rfc3339(&'a mut self, val: bool) {
+        if val {
+            self.timestamp_style = TimestampStyle::Rfc3339;
+        } else if self.timestamp_style == TimestampStyle::Rfc3339 {
+            self.timestamp_style = TimestampStyle::DateTime;
+        }
+    }
+
+    pub fn set_timestamp_style(&'a mut self, style: TimestampStyle) {
+        self.timestamp_style = style;
+    }
+
+    pub fn timestamp_style(&'a self) -> TimestampStyle {
+        self.timestamp_style
+    }
+
+    /// The time since the logger was created
+    pub fn uptime(&'a self) -> Duration {
+        self.created.elapsed()
+    }
+
+    pub fn set_timezone(&'a mut self, timezone: Option<FixedOffset>) {
+        self.timezone = timezone;
+    }
+
+    pub fn timezone(&'a self) -> Option<FixedOffset> {
+        self.timezone
+    }
+
+    pub fn set_run_separator(&'a mut self, separator: Option<String>) {
+        self.run_separator = separator;
+    }
+
+    /// Write the run separator to a log file opened in append mode, size is the size of the
+    /// existing file. Nothing is written to empty files. Returns the number of bytes written.
+    pub fn write_run_separator(&'a self, stream: &mut dyn Write, size: u64) -> u64 {
+        if self.format.is_binary() {
+            // text lines would corrupt the binary stream
+            return 0;
+        }
+        match self.run_separator {
+            Some(ref separator) if size > 0 => {
+                let now: DateTime<FixedOffset> = if let Some(timezone) = self.timezone {
+                    Local::now().with_timezone(&timezone)
+                } else {
+                    Local::now().into()
+                };
+                let line = format!(
+                    "{}\n",
+                    separator.replace("{timestamp}", &now.format("%Y-%m-%d %H:%M:%S").to_string())
+                );
+                if stream.write_all(line.as_bytes()).is_ok() {
+                    line.len() as u64
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn set_strict_modules(&'a mut self, strict: bool) {
+        self.strict_modules = strict;
+    }
+
+    pub fn register_modules(&'a mut self, modules: &[&str]) {
+        for module in modules {
+            self.known_modules.insert(String::from(*module));
+        }
+    }
+
+    /// In strict mode check that module is a registered module or a parent of one
+    pub fn check_module(&'a self, module: &str) -> Result<()> {
+        if !self.strict_modules
+            || module == ALL_MODULES
+            || self.known_modules.iter().any(|known| {
+                known
+                    .strip_prefix(module)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+        {
+            Ok(())
+        } else {
+            Err(Error::with_context(
+                ErrorKind::InvParam,
+                &format!("unknown module '{}'", module),
+            ))
+        }
+    }
+
+    /// Check all modules of level_config, see check_module
+    pub fn check_level_config(&'a self, level_config: &LevelConfig) -> Result<()> {
+        for module in level_config.get_mod_level().keys() {
+            self.check_module(module)?;
+        }
+        Ok(())
+    }
+
+    /// Set the level of module, ```*``` sets the default level
+    pub fn set_mod_level(&'a mut self, module: &str, level: Level) -> &'a Level {
+        if module == ALL_MODULES {
+            self.set_default_level(level);
+            return &self.max_level;
+        }
+        self.mod_level.insert(String::from(module), level);
+        match level.cmp(&self.max_level) {
+            Ordering::Greater => {
+                self.max_level = level;
+            }
+            Ordering::Less => {
+                self.recalculate_max_level();
+            }
+            _ => (),
+        };
+        &self.max_level
+    }
+
+    #[cfg(feature = "config")]
+    pub fn clear_mod_level(&'a mut self) {
+        self.mod_level.clear();
+        self.recalculate_max_level();
+    }
+
+    #[cfg(feature = "config")]
+    pub fn set_mod_config(&'a mut self, mod_config: &HashMap<String, Level>) -> &'a Level {
+        for module in mod_config.keys() {
+            if let Some(level) = mod_config.get(module) {
+                self.mod_level.insert(module.clone(), *level);
+            }
+        }
+        self.recalculate_max_level();
+        &self.max_level
+    }
+
+    pub fn set_level_config(&'a mut self, level_config: &LevelConfig) -> &'a Level {
+        self.set_default_level_filter(level_config.get_default_level());
+        for (module, level) in level_config.get_mod_level() {
+            self.mod_level.insert(module.clone(), *level);
+        }
+        self.recalculate_max_level();
+        &self.max_level
+    }
+
+    /// Replace the default level and all module levels
+    pub fn replace_level_config(&'a mut self, level_config: &LevelConfig) -> &'a Level {
+        self.mod_level.clear();
+        self.set_level_config(level_config)
+    }
+
+    pub fn set_default_level(&'a mut self, level: Level) -> Level {
+        self.default_level = level;
+        self.default_off = false;
+        if level >= self.max_level {
+            self.max_level = level;
+        } else {
+            self.recalculate_max_level()
+        }
+        self.max_level
+    }
+
+    pub fn set_env_default_level(&'a mut self) {
+        self.env_default_level = true;
+    }
+
+    pub fn env_default_level(&'a self) -> bool {
+        self.env_default_level
+    }
+
+    /// Set the default level, LevelFilter::Off drops records of modules without a module level
+    pub fn set_default_level_filter(&'a mut self, level: LevelFilter) -> Level {
+        match level.to_level() {
+            Some(level) => self.set_default_level(level),
+            None => {
+                self.default_off = true;
+                self.recalculate_max_level();
+                self.max_level
+            }
+        }
+    }
+
+    pub fn get_default_level(&'a self) -> Level {
+        self.default_level
+    }
+
+    pub fn default_level_filter(&'a self) -> LevelFilter {
+        if self.default_off {
+            LevelFilter::Off
+        } else {
+            self.default_level.to_level_filter()
+        }
+    }
+
+    pub fn get_log_dest(&'a self) -> &'a LogDestination {
+        &self.log_dest
+    }
+
+    pub fn log_stream(&mut self) -> &mut Option<Box<dyn 'static + Write + Send>> {
+        &mut self.log_stream
+    }
+
+    #[cfg(feature = "config")]
+    pub fn log_path(&self) -> Option<&Path> {
+        self.log_path.as_deref()
+    }
+
+    /// Set the path of the log file that was just opened as log stream, recording its identity
+    pub fn set_log_path(&mut self, path: Option<&Path>) {
+        self.log_path = path.map(|path| path.to_path_buf());
+        self.file_id = path.and_then(file_id);
+    }
+
+    pub fn set_reopen_on_rotate(&mut self, val: bool) {
+        self.reopen_on_rotate = val;
+    }
+
+    // reopen the log file if it was renamed or removed, eg. by logrotate. The file at log_path is
+    // checked at most once per REOPEN_CHECK_INTERVAL.
+    fn check_reopen(&mut self) {
+        if !self.reopen_on_rotate || self.file_checked.elapsed() < REOPEN_CHECK_INTERVAL {
+            return;
+        }
+        self.file_checked = Instant::now();
+        let path = match (&self.log_path, &self.log_stream) {
+            (Some(path), Some(_)) => path.clone(),
+            _ => return,
+        };
+
+        if let (Some(opened), Some(current)) = (self.file_id, file_id(&path)) {
+            if opened == current {
+                return;
+            }
+        }
+
+        if let Some(ref mut stream) = self.log_stream {
+            let _res = stream.flush();
+        }
+        match OpenOptions::new().append(true).create(true).open(&path) {
+            Ok(mut file) => {
+                self.opened_bytes = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                if self.opened_bytes == 0 {
+                    if let Some(header) = self.stream_header() {
+                        if file.write_all(header.as_bytes()).is_ok() {
+                            self.opened_bytes += header.len() as u64;
+                        }
+                    }
+                }
+                self.written = (0, 0);
+                self.file_id = open_file_id(&file);
+                self.log_stream = Some(self.queue_stream(self.file_buffering.wrap(file)));
+            }
+            Err(why) => {
+                eprintln!(
+                    "Failed to reopen log file: '{}', error: {:?}",
+                    path.display(),
+                    why
+                );
+            }
+        }
+    }
+
+    /// Rotate the log file at log_path by size, opened_bytes is the current size of the file
+    pub fn set_rotation(&mut self, rotation: Option<RotationConfig>, opened_bytes: u64) {
+        self.rotation = rotation;
+        self.rotation_failed = None;
+        self.opened_bytes = opened_bytes;
+    }
+
+    /// Open the log file at path in append mode or, with truncate, create it empty
+    pub fn open_file(path: &Path, truncate: bool) -> Result<File> {
+        if truncate {
+            File::create(path)
+        } else {
+            OpenOptions::new().append(true).create(true).open(path)
+        }
+        .upstream_with_context(&format!("Failed to open log file: '{}'", path.display()))
+    }
+
+    /// Log to the file at path with the stream destination dest, see open_file and log_to_file
+    pub fn open_log_file(
+        &mut self,
+        dest: &LogDestination,
+        path: &Path,
+        truncate: bool,
+        buffering: Option<BufferStrategy>,
+        rotation: Option<RotationConfig>,
+    ) -> Result<()> {
+        LoggerParams::check_format(self.format, dest)?;
+        let file = LoggerParams::open_file(path, truncate)?;
+        self.log_to_file(dest, path, file, buffering, rotation)
+    }
+
+    /// Log to file, opened at path, with the stream destination dest.\
+    /// The file is wrapped as set by set_file_buffering unless buffering is given. The header is
+    /// written to empty files and the run separator to files with content, followed by the
+    /// buffered output that was not written to a log file yet.
+    pub fn log_to_file(
+        &mut self,
+        dest: &LogDestination,
+        path: &Path,
+        file: File,
+        buffering: Option<BufferStrategy>,
+        rotation: Option<RotationConfig>,
+    ) -> Result<()> {
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        let mut stream = buffering.unwrap_or(self.file_buffering).wrap(file);
+
+        // flush first, so the buffer holds everything logged so far
+        self.flush();
+        let mut buffer = self.take_unwritten_buffer();
+        if self.format == LogFormat::Csv {
+            // the header of the buffer is written to empty files only
+            if let Some(ref mut buffer) = buffer {
+                if buffer.starts_with(CSV_HEADER.as_bytes()) {
+                    buffer.drain(..CSV_HEADER.len());
+                }
+            }
+        }
+
+        let mut size = size + self.write_run_separator(&mut stream, size);
+        if size == 0 {
+            if let Some(header) = self.stream_header() {
+                if stream.write_all(header.as_bytes()).is_ok() {
+                    size = header.len() as u64;
+                }
+            }
+        }
+        if let Some(buffer) = buffer {
+            stream
+                .write_all(buffer.as_slice())
+                .upstream_with_context(&format!(
+                    "Failed to write buffers to file: '{}'",
+                    path.display()
+                ))?;
+            stream.flush().upstream_with_context(&format!(
+                "Failed to flush buffers to file: '{}'",
+                path.display()
+            ))?;
+            size += buffer.len() as u64;
+        }
+
+        self.replace_log_dest(dest, Some(stream), false)?;
+        self.set_log_path(Some(path));
+        self.set_rotation(rotation, size);
+        Ok(())
+    }
+
+    /// Log to the file at path, creating it on the first write
+    pub fn set_lazy_log_file(&mut self, dest: &LogDestination, path: &Path) -> Result<()> {
+        self.set_log_dest(dest, Some(std::io::sink()))?;
+        self.log_stream = None;
+        self.log_path = Some(path.to_path_buf());
+        self.lazy_open = true;
+        Ok(())
+    }
+
+    pub fn set_async_capacity(&'a mut self, capacity: Option<usize>) -> Result<()> {
+        if capacity == Some(0) {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                "the capacity of the async queue must not be 0",
+            ));
+        }
+        self.async_capacity = capacity;
+        Ok(())
+    }
+
+    pub fn queue_counters(&'a self) -> &'a QueueCounters {
+        &self.queue_counters
+    }
+
+    pub fn set_file_buffering(&'a mut self, strategy: BufferStrategy) -> Result<()> {
+        if strategy == BufferStrategy::Block(0) {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                "the block size of file buffering must not be 0",
+            ));
+        }
+        self.file_buffering = strategy;
+        Ok(())
+    }
+
+    // hand the output of stream to a background thread in async mode
+    fn queue_stream(&self, stream: Box<dyn Write + Send>) -> Box<dyn Write + Send> {
+        if let Some(capacity) = self.async_capacity {
+            Box::new(AsyncWriter::new(
+                stream,
+                capacity,
+                self.queue_counters.clone(),
+            ))
+        } else {
+            stream
+        }
+    }
+
+    // create the log file of a lazily opened log destination
+    fn open_lazy_file(&mut self) {
+        self.lazy_open = false;
+        self.file_id = None;
+        if let Some(ref path) = self.log_path {
+            match File::create(path) {
+                Ok(mut file) => {
+                    self.file_id = open_file_id(&file);
+                    self.opened_bytes = 0;
+                    if let Some(header) = self.stream_header() {
+                        if file.write_all(header.as_bytes()).is_ok() {
+                            self.opened_bytes = header.len() as u64;
+                        }
+                    }
+                    self.log_stream = Some(self.queue_stream(self.file_buffering.wrap(file)));
+                }
+                Err(why) => {
+                    eprintln!(
+                        "Failed to create log file: '{}', error: {:?}",
+                        path.display(),
+                        why
+                    );
+                }
+            }
+        }
+    }
+
+    /// Bytes and lines written to the log stream since it was opened, None if the log destination
+    /// has no log stream
+    pub fn stream_written(&self) -> Option<(u64, u64)> {
+        if self.log_dest.is_stream_dest() && (self.log_stream.is_some() || self.lazy_open) {
+            Some(self.written)
+        } else {
+            None
+        }
+    }
+
+    // write output to the log stream, rotating the log file if it gets too big
+    fn write_stream(&mut self, output: &[u8]) -> std::io::Result<usize> {
+        if self.lazy_open {
+            self.open_lazy_file();
+        } else {
+            self.check_reopen();
+        }
+
+        let res = if let Some(ref mut stream) = self.log_stream {
+            let res = stream.write_all(output).map(|_| output.len());
+            self.unflushed = true;
+            if res.is_ok() {
+                self.written.0 += output.len() as u64;
+                self.written.1 += output.iter().filter(|byte| **byte == b'\n').count() as u64;
+            }
+            res
+        } else {
+            Ok(0)
+        };
+
+        if let Some(ref rotation) = self.rotation {
+            let suspended = matches!(self.rotation_failed,
+                Some(failed) if failed.elapsed() < ROTATION_RETRY_INTERVAL);
+            if !suspended && self.opened_bytes + self.written.0 >= rotation.get_max_bytes() {
+                match self.rotate_file() {
+                    Ok(()) => self.rotation_failed = None,
+                    Err(why) => {
+                        eprintln!("Failed to rotate log file, error: {:?}", why);
+                        self.rotation_failed = Some(Instant::now());
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Rotate the log file and continue logging to a new file at log_path.\
+    /// Without rotation config the file is renamed using a timestamp.
+    pub fn rotate_file(&mut self) -> Result<()> {
+        let path = match self.log_path.clone() {
+            Some(ref path) if self.log_dest.is_stream_dest() => path.clone(),
+            _ => {
+                return Err(Error::with_context(
+                    ErrorKind::InvState,
+                    "The log destination is not a log file",
+                ))
+            }
+        };
+
+        // dropping the stream flushes and closes the file
+        if let Some(ref mut stream) = self.log_stream {
+            let _res = stream.flush();
+        }
+        self.log_stream = None;
+
+        if self.lazy_open && !path.exists() {
+            // nothing has been written yet
+            return Ok(());
+        }
+        self.lazy_open = false;
+
+        let rotate_res = if let Some(ref rotation) = self.rotation {
+            rotate_files(&path, rotation)
+        } else {
+            rotate_timestamped(&path)
+        }
+        .upstream_with_context(&format!("Failed to rotate log file: '{}'", path.display()));
+        self.written = (0, 0);
+
+        // keep logging to the file even if rotation failed
+        let mut stream = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .upstream_with_context(&format!("Failed to open log file: '{}'", path.display()))?;
+        self.opened_bytes = stream
+            .metadata()
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        if self.opened_bytes == 0 {
+            if let Some(header) = self.stream_header() {
+                if stream.write_all(header.as_bytes()).is_ok() {
+                    self.opened_bytes = header.len() as u64;
+                }
+            }
+        }
+        self.file_id = open_file_id(&stream);
+        self.log_stream = Some(self.queue_stream(self.file_buffering.wrap(stream)));
+        rotate_res
+    }
+
+    pub fn describe(&self) -> Vec<(String, String)> {
+        fn level_str(level: Level) -> String {
+            level.to_string().to_lowercase()
+        }
+
+        let mut description = vec![(
+            String::from("default_level"),
+            self.default_level_filter().to_string().to_lowercase(),
+        )];
+
+        let mut modules: Vec<&String> = self.mod_level.keys().collect();
+        modules.sort();
+        for module in modules {
+            description.push((
+                format!("mod_level.{}", module),
+                level_str(self.mod_level[module]),
+            ));
+        }
+
+        if let Some(level) = self.external_level {
+            description.push((String::from("external_level"), level_str(level)));
+        }
+        if let Some(ref prefix) = self.internal_prefix {
+            description.push((String::from("internal_prefix"), prefix.clone()));
+        }
+
+        description.push((String::from("format"), format!("{:?}", self.format)));
+        description.push((String::from("log_dest"), format!("{:?}", self.log_dest)));
+        if self.log_dest.is_stream_dest() {
+            let stream = if let Some(ref path) = self.log_path {
+                path.display().to_string()
+            } else {
+                String::from("<unknown>")
+            };
+            description.push((String::from("log_stream"), stream));
+        }
+
+        for (name, val) in [
+            ("color", self.color),
+            ("timestamp", self.timestamp),
+            ("millis", self.millis),
+            ("brief_info", self.brief_info),
+            ("adaptive_format", self.adaptive_format),
+            ("indent_multiline", self.indent_multiline),
+            ("show_target", self.show_target),
+        ] {
+            description.push((String::from(name), val.to_string()));
+        }
+
+        if let Some(timezone) = self.timezone {
+            description.push((String::from("timezone"), timezone.to_string()));
+        }
+        description.push((String::from("hostname"), self.hostname.clone()));
+
+        if let Some(width) = self.wrap_width {
+            description.push((String::from("wrap_width"), width.to_string()));
+        }
+        if let Some(ref name) = self.show_since {
+            description.push((String::from("show_since"), name.clone()));
+        }
+        if self.callsite_rate > 0 {
+            description.push((
+                String::from("callsite_rate_limit"),
+                self.callsite_rate.to_string(),
+            ));
+        }
+        if let Some(ref columns) = self.columns {
+            let widths: Vec<String> = DEFAULT_FIELD_ORDER
+                .iter()
+                .filter_map(|field| {
+                    columns
+                        .column(*field)
+                        .map(|(width, align)| format!("{:?}={}{:?}", field, width, align))
+                })
+                .collect();
+            description.push((String::from("column_layout"), widths.join(",")));
+        }
+        if self.max_message_len > 0 {
+            description.push((
+                String::from("max_message_len"),
+                self.max_message_len.to_string(),
+            ));
+        }
+        if self.module_max_len > 0 {
+            description.push((
+                String::from("module_max_len"),
+                self.module_max_len.to_string(),
+            ));
+        }
+
+        description
+    }
+
+    pub fn list_destinations(&self) -> Vec<DestinationInfo> {
+        let mut destinations = Vec::new();
+        if self.log_dest.is_stdout() {
+            destinations.push(DestinationInfo::new(DestinationKind::Stdout, None));
+        } else if self.log_dest.is_stderr() {
+            destinations.push(DestinationInfo::new(DestinationKind::Stderr, None));
+        }
+
+        if self.log_dest.is_stream_dest() {
+            if let Some(ref path) = self.log_path {
+                destinations.push(DestinationInfo::new(
+                    DestinationKind::File,
+                    Some(path.display().to_string()),
+                ));
+            } else {
+                destinations.push(DestinationInfo::new(DestinationKind::Stream, None));
+            }
+        }
+        if self.log_dest.is_buffer_dest() || self.buffer_with_file {
+            destinations.push(DestinationInfo::new(DestinationKind::Buffer, None));
+        }
+
+        #[cfg(feature = "gelf")]
+        if let LogDestination::Gelf { ref addr } = self.log_dest {
+            destinations.push(DestinationInfo::new(
+                DestinationKind::Gelf,
+                Some(addr.clone()),
+            ));
+        }
+
+        #[cfg(feature = "otlp")]
+        if let LogDestination::Otlp { ref endpoint } = self.log_dest {
+            destinations.push(DestinationInfo::new(
+                DestinationKind::Otlp,
+                Some(endpoint.clone()),
+            ));
+        }
+
+        if self.audit_stream.is_some() {
+            destinations.push(DestinationInfo::new(
+                DestinationKind::Audit,
+                self.audit_target.clone(),
+            ));
+        }
+
+        for sink in &self.sinks {
+            destinations.push(DestinationInfo::new(
+                DestinationKind::Custom,
+                Some(sink.name().to_owned()),
+            ));
+        }
+
+        destinations
+    }
+
+    pub fn buffer_contents(&self) -> Option<Vec<u8>> {
+        self.log_buffer.as_ref().map(|buffer| buffer.contents())
+    }
+
+    pub fn buffer_dump(&self) -> Option<&Path> {
+        self.buffer_dump.as_deref()
+    }
+
+    pub fn set_buffer_dump(&mut self, path: Option<&Path>) {
+        self.buffer_dump = path.map(PathBuf::from);
+    }
+
+    /// Write the contents of the buffer to the dump file, if both are set.\
+    /// The buffer is left as it is.
+    pub fn dump_buffer(&self) -> Result<()> {
+        if let (Some(ref path), Some(ref buffer)) = (&self.buffer_dump, &self.log_buffer) {
+            fs::write(path, buffer.contents()).upstream_with_context(&format!(
+                "Failed to write log buffer to file: '{}'",
+                path.display()
+            ))?;
+        }
+        Ok(())
+    }
+
+    pub fn retrieve_log_buffer(&mut self) -> Option<Vec<u8>> {
+        if let Some(ref mut buffer) = self.log_buffer {
+            let tmp = buffer.contents();
+            buffer.clear();
+            Some(tmp)
+        } else {
+            None
+        }
+    }
+
+    /// Replace the memory buffer by a ring buffer using storage, keeping the newest contents
+    pub fn set_fixed_buffer(&mut self, storage: Box<[u8]>) -> Result<()> {
+        if storage.is_empty() {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                "the fixed buffer must not be empty",
+            ));
+        }
+        if let Some(ref mut buffer) = self.log_buffer {
+            let contents = buffer.contents();
+            *buffer = LogBuffer::fixed(storage);
+            let _res = buffer.write_all(&contents);
+            Ok(())
+        } else {
+            Err(Error::with_context(
+                ErrorKind::InvState,
+                &format!("log destination {:?} has no buffer", self.log_dest),
+            ))
+        }
+    }
+
+    pub fn buffer_with_file(&'a self) -> bool {
+        self.buffer_with_file
+    }
+
+    /// Take the buffered output that was not written to a log file yet, None while the buffer
+    /// holds a copy of the log file, see set_file_buffer
+    pub fn take_unwritten_buffer(&mut self) -> Option<Vec<u8>> {
+        if self.buffer_with_file {
+            None
+        } else {
+            self.retrieve_log_buffer()
+        }
+    }
+
+    /// Keep the most recent capacity bytes written to the log file in a ring buffer
+    pub fn set_file_buffer(&'a mut self, capacity: usize) -> Result<()> {
+        if capacity == 0 {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                "the buffer capacity must not be 0",
+            ));
+        }
+        self.log_buffer = Some(LogBuffer::fixed(vec![0u8; capacity].into_boxed_slice()));
+        self.buffer_with_file = true;
+        Ok(())
+    }
+
+    pub fn resize_buffer(&'a mut self, capacity: usize) -> Result<()> {
+        if capacity == 0 {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                "the buffer capacity must not be 0",
+            ));
+        }
+        match self.log_buffer {
+            Some(ref mut buffer) => {
+                if buffer.resize(capacity) {
+                    Ok(())
+                } else {
+                    Err(Error::with_context(
+                        ErrorKind::InvState,
+                        "the log buffer is not a fixed size buffer",
+                    ))
+                }
+            }
+            None => Err(Error::with_context(
+                ErrorKind::InvState,
+                &format!("log destination {:?} has no buffer", self.log_dest),
+            )),
+        }
+    }
+
+    /// Write the line to the sinks of the log destination
+    pub fn write_line(&mut self, line: &Line, opts: &SinkOpts) {
+        self.level_counters.record(line.level);
+        if let Some(ref tap) = self.line_tap {
+            // the line as rendered for the first sink of the destination
+            let tap_opts = if self.log_dest.is_stream_dest() {
+                &opts.stream
+            } else if self.log_dest.is_buffer_dest() {
+                &opts.buffer
+            } else {
+                &opts.console
+            };
+            tap(&line.render(tap_opts));
+        }
+
+        // lines are written in one piece to the locked console, so they do not interleave with
+        // output of other threads, eg. from println!
+        let _res = match self.log_dest {
+            LogDestination::Stderr => stderr()
+                .lock()
+                .write_all(line.render(&opts.console).as_bytes()),
+            LogDestination::Stdout => stdout()
+                .lock()
+                .write_all(line.render(&opts.console).as_bytes()),
+            LogDestination::Stream => {
+                if self.log_stream.is_some() || self.lazy_open {
+                    self.write_stream(&line.render_bytes(&opts.stream))
+                        .map(|_| ())
+                } else {
+                    stderr()
+                        .lock()
+                        .write_all(line.render(&opts.console).as_bytes())
+                }
+            }
+            LogDestination::StreamStdout => {
+                let _wres = self.write_stream(&line.render_bytes(&opts.stream));
+                stdout()
+                    .lock()
+                    .write_all(line.render(&opts.console).as_bytes())
+            }
+            LogDestination::StreamStderr => {
+                let _wres = self.write_stream(&line.render_bytes(&opts.stream));
+                stderr()
+                    .lock()
+                    .write_all(line.render(&opts.console).as_bytes())
+            }
+            LogDestination::Buffer => {
+                if let Some(ref mut buffer) = self.log_buffer {
+                    buffer.write_all(&line.render_bytes(&opts.buffer))
+                } else {
+                    stderr()
+                        .lock()
+                        .write_all(line.render(&opts.console).as_bytes())
+                }
+            }
+            LogDestination::BufferStdout => {
+                if let Some(ref mut buffer) = self.log_buffer {
+                    let _wres = buffer.write_all(&line.render_bytes(&opts.buffer));
+                }
+                stdout()
+                    .lock()
+                    .write_all(line.render(&opts.console).as_bytes())
+            }
+            LogDestination::BufferStderr => {
+                if let Some(ref mut buffer) = self.log_buffer {
+                    let _wres = buffer.write_all(&line.render_bytes(&opts.buffer));
+                }
+                stderr()
+                    .lock()
+                    .write_all(line.render(&opts.console).as_bytes())
+            }
+            #[cfg(feature = "gelf")]
+            LogDestination::Gelf { .. } => {
+                if let Some(ref gelf) = self.gelf {
+                    gelf.send(line, &self.hostname);
+                    Ok(())
+                } else {
+                    stderr()
+                        .lock()
+                        .write_all(line.render(&opts.console).as_bytes())
+                }
+            }
+            #[cfg(feature = "otlp")]
+            LogDestination::Otlp { .. } => {
+                if let Some(ref otlp) = self.otlp {
+                    otlp.send(line);
+                    Ok(())
+                } else {
+                    stderr()
+                        .lock()
+                        .write_all(line.render(&opts.console).as_bytes())
+                }
+            }
+        };
+
+        if self.buffer_with_file {
+            if let Some(ref mut buffer) = self.log_buffer {
+                let _res = buffer.write_all(&line.render_bytes(&opts.buffer));
+            }
+        }
+
+        if !self.sinks.is_empty() {
+            let output = line.render_bytes(&opts.stream);
+            for sink in self.sinks.iter_mut() {
+                let _res = sink.write_all(&output);
+            }
+            self.unflushed = true;
+        }
+    }
+
+    /// Write raw output to all sinks of the log destination, bypassing formatting.\
+    /// Raw output is not sent to GELF and OTLP destinations.
+    pub fn write_unformatted(&mut self, output: &[u8]) -> std::io::Result<()> {
+        for sink in self.sinks.iter_mut() {
+            let _res = sink.write_all(output);
+            self.unflushed = true;
+        }
+        if self.buffer_with_file {
+            if let Some(ref mut buffer) = self.log_buffer {
+                let _res = buffer.write_all(output);
+            }
+        }
+        match self.log_dest {
+            LogDestination::Stderr => stderr().lock().write_all(output),
+            LogDestination::Stdout => stdout().lock().write_all(output),
+            LogDestination::Stream => {
+                if self.log_stream.is_some() || self.lazy_open {
+                    self.write_stream(output).map(|_| ())
+                } else {
+                    stderr().lock().write_all(output)
+                }
+            }
+            LogDestination::StreamStdout => {
+                let _wres = self.write_stream(output);
+                stdout().lock().write_all(output)
+            }
+            LogDestination::StreamStderr => {
+                let _wres = self.write_stream(output);
+                stderr().lock().write_all(output)
+            }
+            LogDestination::Buffer => {
+                if let Some(ref mut buffer) = self.log_buffer {
+                    buffer.write_all(output)
+                } else {
+                    stderr().lock().write_all(output)
+                }
+            }
+            LogDestination::BufferStdout => {
+                if let Some(ref mut buffer) = self.log_buffer {
+                    let _wres = buffer.write_all(output);
+                }
+                stdout().lock().write_all(output)
+            }
+            LogDestination::BufferStderr => {
+                if let Some(ref mut buffer) = self.log_buffer {
+                    let _wres = buffer.write_all(output);
+                }
+                stderr().lock().write_all(output)
+            }
+            #[cfg(feature = "gelf")]
+            LogDestination::Gelf { .. } => Ok(()),
+            #[cfg(feature = "otlp")]
+            LogDestination::Otlp { .. } => Ok(()),
+        }
+    }
+
+    /// Add a sink, fails if a sink with the same name exists
+    pub fn add_sink(&'a mut self, sink: Box<dyn LogSink>) -> Result<()> {
+        if self
+            .sinks
+            .iter()
+            .any(|existing| existing.name() == sink.name())
+        {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                &format!("a sink named '{}' was already added", sink.name()),
+            ));
+        }
+        self.sinks.push(sink);
+        Ok(())
+    }
+
+    /// Replace all sinks by sinks, returns the previous sinks after flushing them.\
+    /// Fails if two of the new sinks have the same name, the current sinks are kept then.
+    pub fn swap_sinks(&'a mut self, sinks: Vec<Box<dyn LogSink>>) -> Result<Vec<Box<dyn LogSink>>> {
+        for (index, sink) in sinks.iter().enumerate() {
+            if sinks[..index]
+                .iter()
+                .any(|existing| existing.name() == sink.name())
+            {
+                return Err(Error::with_context(
+                    ErrorKind::InvParam,
+                    &format!("more than one sink is named '{}'", sink.name()),
+                ));
+            }
+        }
+        let mut previous = mem::replace(&mut self.sinks, sinks);
+        for sink in previous.iter_mut() {
+            let _res = sink.flush();
+        }
+        Ok(previous)
+    }
+
+    /// Flush and remove the sink named name, returns false if there is no such sink
+    pub fn remove_sink(&'a mut self, name: &str) -> bool {
+        if let Some(index) = self.sinks.iter().position(|sink| sink.name() == name) {
+            let mut sink = self.sinks.remove(index);
+            let _res = sink.flush();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Set the destination of a module, see Logger::set_mod_dest
+    pub fn set_mod_dest<S: 'static + Write + Send>(
+        &'a mut self,
+        module: &str,
+        dest: &LogDestination,
+        stream: Option<S>,
+        also_global: bool,
+    ) -> Result<()> {
+        let sink = match (dest, stream) {
+            (LogDestination::Stdout, _) => ModSink::Stdout,
+            (LogDestination::Stderr, _) => ModSink::Stderr,
+            (LogDestination::Stream, Some(stream)) => ModSink::Stream(Box::new(stream)),
+            (LogDestination::Stream, None) => {
+                return Err(Error::with_context(
+                    ErrorKind::InvParam,
+                    &format!("no stream given for the destination of module '{}'", module),
+                ))
+            }
+            _ => {
+                return Err(Error::with_context(
+                    ErrorKind::InvParam,
+                    &format!(
+                        "invalid destination {:?} for module '{}', use stdout, stderr or stream",
+                        dest, module
+                    ),
+                ))
+            }
+        };
+        self.clear_mod_dest(module);
+        self.mod_dest
+            .insert(String::from(module), ModDest { sink, also_global });
+        Ok(())
+    }
+
+    pub fn clear_mod_dest(&'a mut self, module: &str) {
+        if let Some(mut mod_dest) = self.mod_dest.remove(module) {
+            let _res = mod_dest.sink.flush();
+        }
+    }
+
+    /// Write line to the destination of module, if any.\
+    /// Returns true if the line must also be written to the log destination.
+    pub fn write_mod_line(&mut self, module: &str, line: &Line, opts: &SinkOpts) -> bool {
+        let mod_dest = match find_mod_entry(&self.mod_dest, module) {
+            Some((mod_path, _)) => self.mod_dest.get_mut(mod_path).unwrap(),
+            None => return true,
+        };
+        let _res = match mod_dest.sink {
+            ModSink::Stream(_) => {
+                self.unflushed = true;
+                mod_dest.sink.write_all(&line.render_bytes(&opts.stream))
+            }
+            _ => mod_dest
+                .sink
+                .write_all(line.render(&opts.console).as_bytes()),
+        };
+        if mod_dest.also_global {
+            return true;
+        }
+        self.level_counters.record(line.level);
+        if let Some(ref tap) = self.line_tap {
+            tap(&line.render(&opts.stream));
+        }
+        false
+    }
+
+    /// Write an audit line to the audit stream if set or to the log destination otherwise
+    pub fn write_audit_line(&mut self, line: &Line, opts: &SinkOpts) {
+        if let Some(ref mut stream) = self.audit_stream {
+            self.level_counters.record(line.level);
+            if let Some(ref tap) = self.line_tap {
+                tap(&line.render(&opts.stream));
+            }
+            let _res = stream.write_all(&line.render_bytes(&opts.stream));
+            self.unflushed = true;
+        } else {
+            self.write_line(line, opts);
+        }
+    }
+
+    /// Flush all sinks, returns true if output written since the last flush was flushed
+    /// successfully
+    pub fn flush(&mut self) -> bool {
+        let unflushed = mem::take(&mut self.unflushed);
+        let mut flushed = true;
+        if let Some(ref mut stream) = self.audit_stream {
+            flushed &= stream.flush().is_ok();
+        }
+
+        for mod_dest in self.mod_dest.values_mut() {
+            flushed &= mod_dest.sink.flush().is_ok();
+        }
+
+        for sink in self.sinks.iter_mut() {
+            flushed &= sink.flush().is_ok();
+        }
+
+        #[cfg(feature = "otlp")]
+        if let Some(ref otlp) = self.otlp {
+            otlp.flush();
+        }
+
+        if self.log_dest.is_stream_dest() {
+            if let Some(ref mut stream) = self.log_stream() {
+                flushed &= stream.flush().is_ok();
+            }
+        }
+
+        if self.log_dest.is_stderr() {
+            let _res = stderr().flush();
+        } else if self.log_dest.is_stdout() {
+            let _res = stdout().flush();
+        }
+        unflushed && flushed
+    }
+
+    // the header written to the start of a new stream, none for binary MessagePack streams
+    fn stream_header(&self) -> Option<String> {
+        if self.format.is_binary() {
+            return None;
+        }
+        if self.format == LogFormat::Csv {
+            Some(CSV_HEADER.to_owned())
+        } else if self.banner_header {
+            self.banner.as_ref().map(|banner| format!("{}\n", banner))
+        } else {
+            None
+        }
+    }
+
+    /// Set the log destination, the header is written to the start of stream
+    pub fn set_log_dest<S: 'static + Write + Send>(
+        &mut self,
+        dest: &LogDestination,
+        stream: Option<S>,
+    ) -> Result<()> {
+        let stream = stream.map(|stream| Box::new(stream) as Box<dyn Write + Send>);
+        self.replace_log_dest(dest, stream, true)
+    }
+
+    // set the log destination, writing the header to stream if with_header is set
+    fn replace_log_dest(
+        &mut self,
+        dest: &LogDestination,
+        stream: Option<Box<dyn Write + Send>>,
+        with_header: bool,
+    ) -> Result<()> {
+        // TODO: flush ?
+        LoggerParams::check_format(self.format, dest)?;
+        if dest.is_stream_dest() && stream.is_none() {
+            return Err(Error::with_context(
+                ErrorKind::InvParam,
+                &format!("no stream given for log destination type {:?}", dest),
+            ));
+        }
+
+        // create the senders first, so a failure leaves the current destination as it is
+        #[cfg(feature = "gelf")]
+        let gelf = match dest {
+            LogDestination::Gelf { ref addr } => Some(GelfSender::new(addr)?),
+            _ => None,
+        };
+        #[cfg(feature = "otlp")]
+        let otlp = match dest {
+            LogDestination::Otlp { ref endpoint } => {
+                Some(OtlpSender::new(endpoint, &self.hostname)?)
+            }
+            _ => None,
+        };
+
+        self.flush();
+        self.written = (0, 0);
+        if self.buffer_with_file {
+            self.buffer_with_file = false;
+            self.log_buffer = None;
+        }
+
+        if self.color && dest.is_console() && !enable_virtual_terminal() {
+            self.color = false;
+        }
+
+        #[cfg(feature = "gelf")]
+        {
+            self.gelf = gelf;
+        }
+        #[cfg(feature = "otlp")]
+        {
+            self.otlp = otlp;
+        }
+
+        if let Some(mut stream) = stream.filter(|_| dest.is_stream_dest()) {
+            if let Some(header) = self.stream_header().filter(|_| with_header) {
+                let _res = stream.write_all(header.as_bytes());
+            }
+            self.log_dest = dest.clone();
+            self.log_stream = Some(self.queue_stream(stream));
+            self.log_path = None;
+            self.file_id = None;
+            self.rotation = None;
+            self.lazy_open = false;
+            Ok(())
+        } else if dest.is_buffer_dest() {
+            self.log_dest = dest.clone();
+            self.log_stream = None;
+            self.log_path = None;
+            self.rotation = None;
+            self.lazy_open = false;
+            if self.log_buffer.is_none() {
+                let mut buffer = LogBuffer::new();
+                if self.format == LogFormat::Csv {
+                    let _res = buffer.write_all(CSV_HEADER.as_bytes());
+                }
+                self.log_buffer = Some(buffer);
+            }
+            Ok(())
+        } else {
+            self.log_stream = None;
+            self.log_path = None;
+            self.rotation = None;
+            self.lazy_open = false;
+            self.log_dest = dest.clone();
+            if self.log_buffer.is_some() {
+                self.log_buffer = None;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The identity of a file, device and inode on unix
+type FileId = (u64, u64);
+
+/// The identity of the file at path, None if it does not exist
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+/// The identity of the file at path, None if it does not exist.\
+/// Open files can't be renamed on other platforms, so only removed files are detected.
+#[cfg(not(unix))]
+fn file_id(path: &Path) -> Option<FileId> {
+    fs::metadata(path).ok().map(|_| (0, 0))
+}
+
+/// The identity of an open file
+#[cfg(unix)]
+fn open_file_id(file: &File) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    file.metadata()
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+/// The identity of an open file
+#[cfg(not(unix))]
+fn open_file_id(_file: &File) -> Option<FileId> {
+    Some((0, 0))
+}
+
+/// Enable ANSI escape sequences on the Windows console, returns false if the console can't do it.\
+/// This is only attempted once, later calls return the first result.
+#[cfg(windows)]
+fn enable_virtual_terminal() -> bool {
+    static VIRTUAL_TERMINAL: OnceLock<bool> = OnceLock::new();
+    *VIRTUAL_TERMINAL.get_or_init(|| colored::control::set_virtual_terminal(true).is_ok())
+}
+
+/// ANSI escape sequences work on all other consoles
+#[cfg(not(windows))]
+fn enable_virtual_terminal() -> bool {
+    true
+}
+
+/// The host name of the machine, taken from HOSTNAME or the system
+fn detect_hostname() -> String {
+    if let Ok(hostname) = env::var("HOSTNAME") {
+        return hostname;
+    }
+    for path in ["/proc/sys/kernel/hostname", "/etc/hostname"] {
+        if let Ok(hostname) = fs::read_to_string(path) {
+            let hostname = hostname.trim();
+            if !hostname.is_empty() {
+                return hostname.to_owned();
             }
-            Ok(())
         }
     }
+    String::from("localhost")
 }
@@ -0,0 +1,281 @@
+use log::Level;
+use std::io::{Read, Write};
+use std::mem;
+use std::net::TcpStream;
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::format::Line;
+use crate::timer::Timer;
+
+// batches are sent at least this often
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+// batches are sent right away once they hold this many records
+const MAX_BATCH: usize = 512;
+// the number of batches waiting to be sent, further batches are dropped
+const MAX_QUEUED_BATCHES: usize = 8;
+// timeout for connecting to and talking to the collector
+const TIMEOUT: Duration = Duration::from_secs(5);
+// the default path of the OTLP/HTTP logs endpoint
+const LOGS_PATH: &str = "/v1/logs";
+
+// protobuf wire types
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LEN: u8 = 2;
+
+/// The collector endpoint, only plain HTTP is supported
+struct Endpoint {
+    host: String,
+    path: String,
+}
+
+/// Sends records as OTLP log records via HTTP/protobuf to an OpenTelemetry collector.\
+/// Records are batched and handed to a background thread every FLUSH_INTERVAL or once MAX_BATCH
+/// records are queued. Only the background thread talks to the collector, so a slow collector
+/// does not hold up logging. Batches are dropped if MAX_QUEUED_BATCHES are waiting to be sent.
+pub(crate) struct OtlpSender {
+    resource: Arc<Mutex<Vec<u8>>>,
+    batch: Arc<Mutex<Vec<Vec<u8>>>>,
+    queue: SyncSender<Vec<Vec<u8>>>,
+    timer: Timer,
+}
+
+impl OtlpSender {
+    pub fn new(endpoint: &str, host: &str) -> Result<OtlpSender> {
+        let endpoint = parse_endpoint(endpoint)?;
+        let resource = Arc::new(Mutex::new(resource(host)));
+        let (queue, receiver) = sync_channel::<Vec<Vec<u8>>>(MAX_QUEUED_BATCHES);
+
+        let thread_resource = resource.clone();
+        thread::spawn(move || {
+            // exits once the sender is dropped and the queue is drained
+            for records in receiver {
+                let request = export_request(&thread_resource.lock().unwrap(), &records);
+                if let Err(why) = post(&endpoint, &request) {
+                    eprintln!(
+                        "Failed to send {} log records to OTLP endpoint '{}{}', error: {}",
+                        records.len(),
+                        endpoint.host,
+                        endpoint.path,
+                        why
+                    );
+                }
+            }
+        });
+
+        let sender = OtlpSender {
+            resource,
+            batch: Arc::new(Mutex::new(Vec::new())),
+            queue,
+            timer: Timer::new(),
+        };
+
+        let batch = sender.batch.clone();
+        let queue = sender.queue.clone();
+        sender.timer.start(FLUSH_INTERVAL, move || {
+            queue_batch(&batch, &queue);
+        });
+        Ok(sender)
+    }
+
+    /// Update the host name reported as resource attribute
+    pub fn set_host(&self, host: &str) {
+        *self.resource.lock().unwrap() = resource(host);
+    }
+
+    pub fn send(&self, line: &Line) {
+        let full = {
+            let mut batch = self.batch.lock().unwrap();
+            batch.push(log_record(line));
+            batch.len() >= MAX_BATCH
+        };
+        if full {
+            self.flush();
+        }
+    }
+
+    /// Hand all batched records to the background thread to be sent now
+    pub fn flush(&self) {
+        queue_batch(&self.batch, &self.queue);
+    }
+}
+
+impl Drop for OtlpSender {
+    fn drop(&mut self) {
+        self.timer.stop();
+        self.flush();
+    }
+}
+
+// split an endpoint like http://collector:4318/v1/logs into host and path
+fn parse_endpoint(endpoint: &str) -> Result<Endpoint> {
+    if endpoint.starts_with("https://") {
+        return Err(Error::with_context(
+            ErrorKind::InvParam,
+            &format!("OTLP endpoint '{}': https is not supported", endpoint),
+        ));
+    }
+    let address = endpoint.strip_prefix("http://").unwrap_or(endpoint);
+    let (host, path) = match address.find('/') {
+        Some(index) => address.split_at(index),
+        None => (address, LOGS_PATH),
+    };
+    if host.is_empty() {
+        return Err(Error::with_context(
+            ErrorKind::InvParam,
+            &format!("OTLP endpoint '{}' has no host", endpoint),
+        ));
+    }
+    let host = if host.contains(':') {
+        host.to_owned()
+    } else {
+        format!("{}:4318", host)
+    };
+    Ok(Endpoint {
+        host,
+        path: path.to_owned(),
+    })
+}
+
+// hand the batched records to the background thread, dropping them if its queue is full
+fn queue_batch(batch: &Mutex<Vec<Vec<u8>>>, queue: &SyncSender<Vec<Vec<u8>>>) {
+    let records = mem::take(&mut *batch.lock().unwrap());
+    if records.is_empty() {
+        return;
+    }
+    if let Err(TrySendError::Full(records)) = queue.try_send(records) {
+        eprintln!(
+            "Failed to send {} log records to the OTLP endpoint, error: too many batches queued",
+            records.len()
+        );
+    }
+}
+
+fn post(endpoint: &Endpoint, body: &[u8]) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(&endpoint.host)?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let header = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/x-protobuf\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        endpoint.path,
+        endpoint.host,
+        body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    // only the status line matters
+    let mut response = [0u8; 64];
+    let count = stream.read(&mut response)?;
+    let status_line = String::from_utf8_lossy(&response[..count]);
+    match status_line.split(' ').nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(std::io::Error::other(format!(
+            "unexpected response '{}'",
+            status_line.lines().next().unwrap_or("")
+        ))),
+    }
+}
+
+// ExportLogsServiceRequest with a single ResourceLogs and ScopeLogs, the records are observed now
+fn export_request(resource: &[u8], records: &[Vec<u8>]) -> Vec<u8> {
+    let observed = unix_nanos(SystemTime::now());
+    let mut scope = Vec::new();
+    put_bytes(&mut scope, 1, &string_field(1, env!("CARGO_PKG_NAME")));
+    let mut scope_logs = Vec::new();
+    put_bytes(&mut scope_logs, 1, &scope);
+    for record in records {
+        let mut record = record.clone();
+        put_fixed64(&mut record, 11, observed);
+        put_bytes(&mut scope_logs, 2, &record);
+    }
+
+    let mut resource_logs = Vec::new();
+    put_bytes(&mut resource_logs, 1, resource);
+    put_bytes(&mut resource_logs, 2, &scope_logs);
+
+    let mut request = Vec::new();
+    put_bytes(&mut request, 1, &resource_logs);
+    request
+}
+
+// Resource with the host name attribute
+fn resource(host: &str) -> Vec<u8> {
+    let mut resource = Vec::new();
+    put_bytes(&mut resource, 1, &key_value("host.name", host));
+    resource
+}
+
+// LogRecord of line, the observed time is added when it is sent, see export_request
+fn log_record(line: &Line) -> Vec<u8> {
+    let mut record = Vec::new();
+    put_fixed64(&mut record, 1, unix_nanos(line.time));
+    put_varint_field(&mut record, 2, severity_number(line.level));
+    put_bytes(&mut record, 3, line.level.as_str().as_bytes());
+    put_bytes(&mut record, 5, &string_field(1, &line.message));
+    put_bytes(&mut record, 6, &key_value("code.namespace", &line.module));
+    record
+}
+
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// Map the log level to an OTLP severity number
+fn severity_number(level: Level) -> u64 {
+    match level {
+        Level::Trace => 1,
+        Level::Debug => 5,
+        Level::Info => 9,
+        Level::Warn => 13,
+        Level::Error => 17,
+    }
+}
+
+// KeyValue with a string AnyValue
+fn key_value(key: &str, value: &str) -> Vec<u8> {
+    let mut key_value = string_field(1, key);
+    put_bytes(&mut key_value, 2, &string_field(1, value));
+    key_value
+}
+
+fn string_field(field: u32, value: &str) -> Vec<u8> {
+    let mut output = Vec::new();
+    put_bytes(&mut output, field, value.as_bytes());
+    output
+}
+
+fn put_tag(output: &mut Vec<u8>, field: u32, wire_type: u8) {
+    put_varint(output, ((field as u64) << 3) | wire_type as u64);
+}
+
+fn put_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn put_varint_field(output: &mut Vec<u8>, field: u32, value: u64) {
+    put_tag(output, field, WIRE_VARINT);
+    put_varint(output, value);
+}
+
+fn put_fixed64(output: &mut Vec<u8>, field: u32, value: u64) {
+    put_tag(output, field, WIRE_FIXED64);
+    output.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_bytes(output: &mut Vec<u8>, field: u32, value: &[u8]) {
+    put_tag(output, field, WIRE_LEN);
+    put_varint(output, value.len() as u64);
+    output.extend_from_slice(value);
+}
@@ -0,0 +1,155 @@
+//! Log line patterns as used by the log_format! macro.\
+//! A pattern is a string containing the placeholders ```{timestamp}```, ```{level}```,
+//! ```{module}``` and ```{message}```, literal braces are written as ```{{``` and ```}}```.
+
+use std::sync::Arc;
+
+/// The fields of a log record passed to a format function
+pub struct LogFields<'a> {
+    /// the timestamp, empty if timestamps are disabled
+    pub timestamp: &'a str,
+    /// the level label, eg. ```INFO``` or ```AUDIT```
+    pub level: &'a str,
+    /// the module path
+    pub module: &'a str,
+    /// the log message
+    pub message: &'a str,
+}
+
+/// A function formatting a log line from the record fields, see Logger::set_format_fn
+pub type FormatFn = Box<dyn Fn(&LogFields) -> String + Send + Sync>;
+
+pub(crate) type SharedFormatFn = Arc<dyn Fn(&LogFields) -> String + Send + Sync>;
+
+enum Token {
+    Text(String),
+    Timestamp,
+    Level,
+    Module,
+    Message,
+}
+
+const PLACEHOLDERS: &[&str] = &["timestamp", "level", "module", "message"];
+
+const fn is_placeholder(pattern: &[u8], start: usize, end: usize) -> bool {
+    let mut index = 0;
+    while index < PLACEHOLDERS.len() {
+        let name = PLACEHOLDERS[index].as_bytes();
+        if name.len() == end - start {
+            let mut pos = 0;
+            while pos < name.len() && name[pos] == pattern[start + pos] {
+                pos += 1;
+            }
+            if pos == name.len() {
+                return true;
+            }
+        }
+        index += 1;
+    }
+    false
+}
+
+/// Validate a pattern, panics on invalid patterns so it fails when evaluated at compile time
+#[doc(hidden)]
+pub const fn check_pattern(pattern: &str) {
+    let pattern = pattern.as_bytes();
+    let mut pos = 0;
+    while pos < pattern.len() {
+        if pattern[pos] == b'{' {
+            if pos + 1 < pattern.len() && pattern[pos + 1] == b'{' {
+                pos += 2;
+                continue;
+            }
+            let start = pos + 1;
+            let mut end = start;
+            while end < pattern.len() && pattern[end] != b'}' {
+                end += 1;
+            }
+            if end == pattern.len() {
+                panic!("log_format: unclosed placeholder");
+            }
+            if !is_placeholder(pattern, start, end) {
+                panic!("log_format: unknown placeholder, expected one of {{timestamp}}, {{level}}, {{module}} or {{message}}");
+            }
+            pos = end + 1;
+        } else if pattern[pos] == b'}' {
+            if pos + 1 < pattern.len() && pattern[pos + 1] == b'}' {
+                pos += 2;
+            } else {
+                panic!("log_format: unmatched '}}', use '}}}}' for a literal brace");
+            }
+        } else {
+            pos += 1;
+        }
+    }
+}
+
+// split a pattern that passed check_pattern into tokens
+fn parse_pattern(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = pattern;
+    while let Some(ch) = rest.chars().next() {
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push(ch);
+            rest = &rest[2..];
+        } else if ch == '{' {
+            let end = rest.find('}').unwrap();
+            let token = match &rest[1..end] {
+                "timestamp" => Token::Timestamp,
+                "level" => Token::Level,
+                "module" => Token::Module,
+                _ => Token::Message,
+            };
+            if !text.is_empty() {
+                tokens.push(Token::Text(text.clone()));
+                text.clear();
+            }
+            tokens.push(token);
+            rest = &rest[end + 1..];
+        } else {
+            text.push(ch);
+            rest = &rest[ch.len_utf8()..];
+        }
+    }
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    tokens
+}
+
+/// Create a format function from a pattern validated by check_pattern
+#[doc(hidden)]
+pub fn pattern_format_fn(pattern: &'static str) -> FormatFn {
+    let tokens = parse_pattern(pattern);
+    Box::new(move |fields: &LogFields| {
+        let mut line = String::new();
+        for token in &tokens {
+            match token {
+                Token::Text(text) => line.push_str(text),
+                Token::Timestamp => line.push_str(fields.timestamp),
+                Token::Level => line.push_str(fields.level),
+                Token::Module => line.push_str(fields.module),
+                Token::Message => line.push_str(fields.message),
+            }
+        }
+        line
+    })
+}
+
+/// Create a format function for Logger::set_format_fn from a pattern checked at compile time.\
+/// The pattern may contain the placeholders ```{timestamp}```, ```{level}```, ```{module}``` and
+/// ```{message}```, literal braces are written as ```{{``` and ```}}```. Invalid patterns fail to
+/// compile.
+///
+/// ```
+/// use mod_logger::{log_format, Logger};
+/// Logger::set_format_fn(Some(log_format!("{timestamp} {level} <{module}> {message}")));
+/// ```
+#[macro_export]
+macro_rules! log_format {
+    ($pattern:literal) => {{
+        const _: () = $crate::pattern::check_pattern($pattern);
+        $crate::pattern::pattern_format_fn($pattern)
+    }};
+}
@@ -0,0 +1,106 @@
+use chrono::Local;
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// Size based rotation of log files, see Logger::set_rotating_log_file.\
+/// When the log file exceeds max_bytes it is renamed to ```<file>.1```, older files are shifted
+/// to ```<file>.2``` up to ```<file>.<max_files>``` and removed beyond that.
+#[derive(Debug, Clone)]
+pub struct RotationConfig {
+    max_bytes: u64,
+    max_files: usize,
+    total_max_bytes: Option<u64>,
+}
+
+impl<'a> RotationConfig {
+    /// Rotate the log file when it exceeds max_bytes, keeping 5 rotated files
+    pub fn new(max_bytes: u64) -> RotationConfig {
+        RotationConfig {
+            max_bytes,
+            max_files: 5,
+            total_max_bytes: None,
+        }
+    }
+
+    /// Set the number of rotated files to keep
+    pub fn set_max_files(&'a mut self, max_files: usize) -> &'a mut RotationConfig {
+        self.max_files = max_files;
+        self
+    }
+
+    /// Cap the total size of the log file and the rotated files.\
+    /// The active log file is rotated when it reaches max_bytes, so max_bytes of total_max_bytes
+    /// are reserved for it. The oldest rotated files are removed until they fit into the rest,
+    /// even if fewer than max_files are kept. The total may be exceeded by the size of the record
+    /// that triggers the rotation, until the rotation completes.
+    pub fn set_total_max_bytes(
+        &'a mut self,
+        total_max_bytes: Option<u64>,
+    ) -> &'a mut RotationConfig {
+        self.total_max_bytes = total_max_bytes;
+        self
+    }
+
+    pub(crate) fn get_max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), index))
+}
+
+/// Rename the log file to the first rotated file, shifting and removing older files
+pub(crate) fn rotate_files(path: &Path, config: &RotationConfig) -> Result<()> {
+    if config.max_files == 0 {
+        return fs::remove_file(path);
+    }
+
+    let oldest = rotated_path(path, config.max_files);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for index in (1..config.max_files).rev() {
+        let from = rotated_path(path, index);
+        if from.exists() {
+            fs::rename(&from, rotated_path(path, index + 1))?;
+        }
+    }
+    fs::rename(path, rotated_path(path, 1))?;
+
+    if let Some(total_max_bytes) = config.total_max_bytes {
+        // the new log file may grow up to max_bytes before it is rotated again
+        let rotated_max_bytes = total_max_bytes.saturating_sub(config.max_bytes);
+        let mut sizes = Vec::new();
+        for index in 1..=config.max_files {
+            match fs::metadata(rotated_path(path, index)) {
+                Ok(metadata) => sizes.push(metadata.len()),
+                Err(_why) => break,
+            }
+        }
+        let mut total: u64 = sizes.iter().sum();
+        while total > rotated_max_bytes {
+            if let Some(size) = sizes.pop() {
+                fs::remove_file(rotated_path(path, sizes.len() + 1))?;
+                total -= size;
+            } else {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rename the log file to ```<file>.<timestamp>```, used when no rotation is configured
+pub(crate) fn rotate_timestamped(path: &Path) -> Result<()> {
+    let timestamp = Local::now().format("%Y%m%d-%H%M%S");
+    let mut rotated = PathBuf::from(format!("{}.{}", path.display(), timestamp));
+    let mut index = 1;
+    // don't overwrite a file rotated within the same second
+    while rotated.exists() {
+        rotated = PathBuf::from(format!("{}.{}-{}", path.display(), timestamp, index));
+        index += 1;
+    }
+    fs::rename(path, rotated)
+}
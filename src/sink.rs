@@ -0,0 +1,10 @@
+use std::io::Write;
+
+/// A destination defined by the application, eg. an uploader or a database, see
+/// Logger::add_sink.\
+/// Sinks receive every line written to the log destination, rendered like for log files without
+/// color and including the line terminator.
+pub trait LogSink: Write + Send {
+    /// The name of the sink, used by Logger::remove_sink and Logger::list_destinations
+    fn name(&self) -> &str;
+}
@@ -0,0 +1,257 @@
+use log::Level;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The mechanisms that can suppress a record that would otherwise have been logged
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum Suppression {
+    RateLimited,
+}
+
+/// A snapshot of the number of records suppressed, retrieved by Logger::suppression_stats
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SuppressionStats {
+    /// records dropped by the rate limiter
+    pub rate_limited: u64,
+    /// total number of suppressed records by module
+    pub per_module: HashMap<String, u64>,
+}
+
+pub(crate) struct SuppressionCounters {
+    rate_limited: AtomicU64,
+    per_module: Mutex<HashMap<String, u64>>,
+}
+
+impl SuppressionCounters {
+    pub fn new() -> SuppressionCounters {
+        SuppressionCounters {
+            rate_limited: AtomicU64::new(0),
+            per_module: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn record(&self, suppression: Suppression, module: &str) {
+        let counter = match suppression {
+            Suppression::RateLimited => &self.rate_limited,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        let mut per_module = self.per_module.lock().unwrap();
+        if let Some(count) = per_module.get_mut(module) {
+            *count += 1;
+        } else {
+            per_module.insert(String::from(module), 1);
+        }
+    }
+
+    pub fn snapshot(&self) -> SuppressionStats {
+        SuppressionStats {
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            per_module: self.per_module.lock().unwrap().clone(),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.rate_limited.store(0, Ordering::Relaxed);
+        self.per_module.lock().unwrap().clear();
+    }
+}
+
+/// A snapshot of the time spent logging records, retrieved by Logger::timing_stats
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimingStats {
+    /// the number of records measured
+    pub count: u64,
+    /// the total time spent logging in nanoseconds
+    pub total_nanos: u64,
+    /// the longest time spent logging a single record in nanoseconds
+    pub max_nanos: u64,
+}
+
+impl TimingStats {
+    /// The average time spent logging a record in nanoseconds, 0 if nothing was measured
+    pub fn mean_nanos(&self) -> u64 {
+        self.total_nanos.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+pub(crate) struct TimingCounters {
+    enabled: AtomicBool,
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl TimingCounters {
+    pub fn new() -> TimingCounters {
+        TimingCounters {
+            enabled: AtomicBool::new(false),
+            count: AtomicU64::new(0),
+            total_nanos: AtomicU64::new(0),
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> TimingStats {
+        TimingStats {
+            count: self.count.load(Ordering::Relaxed),
+            total_nanos: self.total_nanos.load(Ordering::Relaxed),
+            max_nanos: self.max_nanos.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.total_nanos.store(0, Ordering::Relaxed);
+        self.max_nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the async queue metrics, retrieved by Logger::async_queue_stats
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueStats {
+    /// records currently waiting to be written
+    pub depth: usize,
+    /// the largest depth seen
+    pub high_water: usize,
+    /// records dropped because the queue was full
+    pub dropped: u64,
+}
+
+pub(crate) struct QueueCounters {
+    depth: AtomicUsize,
+    high_water: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+impl QueueCounters {
+    pub fn new() -> QueueCounters {
+        QueueCounters {
+            depth: AtomicUsize::new(0),
+            high_water: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a record about to be queued, returns the new depth
+    pub fn enqueued(&self) -> usize {
+        self.depth.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Update the high water mark once a record was queued at depth
+    pub fn queued(&self, depth: usize) {
+        self.high_water.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    pub fn dequeued(&self) {
+        self.depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn dropped(&self) {
+        self.depth.fetch_sub(1, Ordering::Relaxed);
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> QueueStats {
+        QueueStats {
+            depth: self.depth.load(Ordering::Relaxed),
+            high_water: self.high_water.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reset high water mark and dropped records, the depth is kept
+    pub fn reset(&self) {
+        self.high_water
+            .store(self.depth.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.dropped.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the number of records written per level, retrieved by Logger::level_stats
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelStats {
+    /// error records written
+    pub error: u64,
+    /// warn records written
+    pub warn: u64,
+    /// info records written
+    pub info: u64,
+    /// debug records written
+    pub debug: u64,
+    /// trace records written
+    pub trace: u64,
+}
+
+impl LevelStats {
+    /// A one line summary of warnings and errors, eg. ```completed with 3 warnings, 1 error```
+    pub fn summary(&self) -> String {
+        fn count(count: u64, what: &str) -> String {
+            if count == 1 {
+                format!("{} {}", count, what)
+            } else {
+                format!("{} {}s", count, what)
+            }
+        }
+        format!(
+            "completed with {}, {}",
+            count(self.warn, "warning"),
+            count(self.error, "error")
+        )
+    }
+}
+
+pub(crate) struct LevelCounters {
+    // indexed by level, error first
+    counts: [AtomicU64; 5],
+}
+
+impl LevelCounters {
+    pub fn new() -> LevelCounters {
+        LevelCounters {
+            counts: Default::default(),
+        }
+    }
+
+    pub fn record(&self, level: Level) {
+        self.counts[level as usize - 1].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> LevelStats {
+        let count = |level: Level| self.counts[level as usize - 1].load(Ordering::Relaxed);
+        LevelStats {
+            error: count(Level::Error),
+            warn: count(Level::Warn),
+            info: count(Level::Info),
+            debug: count(Level::Debug),
+            trace: count(Level::Trace),
+        }
+    }
+
+    pub fn reset(&self) {
+        for count in &self.counts {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+}
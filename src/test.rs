@@ -1,6 +1,8 @@
-use log::{info, warn};
-use std::fs::File;
+use log::{info, warn, Record};
+use std::fs::{self, File};
 use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::thread;
 
 use ::mod_logger::{Level, LogDestination, Logger, NO_STREAM};
 
@@ -29,7 +31,115 @@ mod test_mod {
     }
 }
 
+// log from several threads and check that the records in the buffer are ordered
+fn test_concurrent_ordering() {
+    const THREADS: usize = 4;
+    const RECORDS: usize = 100;
+
+    Logger::set_log_dest(&LogDestination::Buffer, NO_STREAM).unwrap();
+    Logger::set_default_level(Level::Info);
+    Logger::set_millis(true);
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|thread_idx| {
+            thread::spawn(move || {
+                for seq in 0..RECORDS {
+                    info!("thread {} seq {}", thread_idx, seq);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let buffer = String::from_utf8(Logger::get_buffer().unwrap()).unwrap();
+    let mut last_timestamp = String::new();
+    let mut last_seq = [None; THREADS];
+    let mut count = 0;
+    for line in buffer.lines() {
+        // eg. 2020-01-01 12:00:00.123 INFO  [test] thread 1 seq 5
+        let timestamp = &line[..23];
+        assert!(
+            timestamp >= last_timestamp.as_str(),
+            "timestamps out of order: {}",
+            line
+        );
+        last_timestamp = timestamp.to_owned();
+
+        let fields: Vec<&str> = line.split(' ').collect();
+        let thread_idx: usize = fields[fields.len() - 3].parse().unwrap();
+        let seq: usize = fields[fields.len() - 1].parse().unwrap();
+        assert_eq!(
+            last_seq[thread_idx].map_or(0, |last| last + 1),
+            seq,
+            "records out of order: {}",
+            line
+        );
+        last_seq[thread_idx] = Some(seq);
+        count += 1;
+    }
+    assert_eq!(count, THREADS * RECORDS);
+
+    Logger::set_millis(false);
+}
+
+// check the module names used to look up module levels, the executable's crate is named test
+fn test_module_names() {
+    Logger::set_log_dest(&LogDestination::Buffer, NO_STREAM).unwrap();
+    Logger::set_default_level(Level::Warn);
+    Logger::set_mod_level("main", Level::Debug);
+    Logger::set_mod_level("foo", Level::Debug);
+    Logger::set_mod_level("dep_crate::bar", Level::Debug);
+
+    for module in [
+        "test",
+        "test::foo",
+        "test::other",
+        "dep_crate::bar",
+        "dep_crate::baz",
+    ] {
+        log::logger().log(
+            &Record::builder()
+                .level(Level::Debug)
+                .module_path(Some(module))
+                .args(format_args!("from {}", module))
+                .build(),
+        );
+    }
+
+    let buffer = String::from_utf8(Logger::get_buffer().unwrap()).unwrap();
+    let logged: Vec<&str> = buffer
+        .lines()
+        .map(|line| line.rsplit(' ').next().unwrap())
+        .collect();
+    assert_eq!(logged, vec!["test", "test::foo", "dep_crate::bar"]);
+}
+
+// check that output is colored even if the colored crate is told not to color anything
+fn test_color_override() {
+    colored::control::set_override(false);
+    Logger::set_log_file(&LogDestination::Stream, Path::new("log_color.txt"), false).unwrap();
+    Logger::set_default_level(Level::Info);
+    Logger::set_color(true);
+
+    warn!("colored despite the override");
+    Logger::flush();
+
+    let output = fs::read_to_string("log_color.txt").unwrap();
+    assert!(
+        output.contains("\x1b[33m") && output.contains("\x1b[0m"),
+        "output not colored: {:?}",
+        output
+    );
+
+    Logger::set_color(false);
+    colored::control::unset_override();
+}
+
 fn main() {
+    test_concurrent_ordering();
+
     Logger::set_default_level(Level::Info);
 
     info!("Logger initialized1");
@@ -65,5 +175,6 @@ fn main() {
             .unwrap();
     }
 
-
+    test_module_names();
+    test_color_override();
 }
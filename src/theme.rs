@@ -0,0 +1,129 @@
+use colored::Color;
+use std::env;
+
+/// Color presets for Logger::set_theme
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Theme {
+    /// the classic colors: red, yellow, green, cyan, blue
+    Default,
+    /// muted colors inspired by the solarized palette
+    Solarized,
+    /// no colors at all
+    Monochrome,
+    /// bright colors for dark or low contrast terminals
+    HighContrast,
+}
+
+// level colors by theme in order error, warn, info, debug, trace, None disables color
+const THEMES: &[(Theme, Option<[Color; 5]>)] = &[
+    (Theme::Default, Some(DEFAULT_LEVEL_COLORS)),
+    (
+        Theme::Solarized,
+        Some([
+            Color::Red,
+            Color::Yellow,
+            Color::Cyan,
+            Color::Blue,
+            Color::BrightBlack,
+        ]),
+    ),
+    (Theme::Monochrome, None),
+    (
+        Theme::HighContrast,
+        Some([
+            Color::BrightRed,
+            Color::BrightYellow,
+            Color::BrightGreen,
+            Color::BrightCyan,
+            Color::BrightWhite,
+        ]),
+    ),
+];
+
+pub(crate) const DEFAULT_LEVEL_COLORS: [Color; 5] = [
+    Color::Red,
+    Color::Yellow,
+    Color::Green,
+    Color::Cyan,
+    Color::Blue,
+];
+
+// colors modules are mapped to by Logger::set_color_by_module, red is left to errors
+pub(crate) const DEFAULT_MODULE_PALETTE: [Color; 6] = [
+    Color::Green,
+    Color::Cyan,
+    Color::Blue,
+    Color::Magenta,
+    Color::Yellow,
+    Color::BrightBlue,
+];
+
+/// Wrap text in the ANSI escape sequences for color.\
+/// Unlike colored's Colorize this ignores the global override of the colored crate, which other
+/// crates in the same binary may change, so Logger::set_color stays authoritative.
+pub(crate) fn ansi_color(text: &str, color: Color) -> String {
+    format!("\x1b[{}m{}\x1b[0m", color.to_fg_str(), text)
+}
+
+/// The color of module from palette, stable across runs.\
+/// palette must not be empty.
+pub(crate) fn module_color(palette: &[Color], module: &str) -> Color {
+    // FNV-1a, std hashers are not guaranteed to be stable
+    let hash = module.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    palette[(hash % palette.len() as u64) as usize]
+}
+
+impl Theme {
+    /// The level colors of the theme, None if the theme disables color
+    pub(crate) fn level_colors(&self) -> Option<[Color; 5]> {
+        THEMES
+            .iter()
+            .find(|(theme, _)| theme == self)
+            .and_then(|(_, colors)| *colors)
+    }
+}
+
+/// The background of the terminal, see Logger::set_background
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Background {
+    /// light background, eg. black on white
+    Light,
+    /// dark background, eg. white on black
+    Dark,
+}
+
+// level colors that stay readable on a light background
+const LIGHT_LEVEL_COLORS: [Color; 5] = [
+    Color::Red,
+    Color::Magenta,
+    Color::Blue,
+    Color::BrightBlack,
+    Color::Black,
+];
+
+impl Background {
+    /// Detect the terminal background from the COLORFGBG environment variable.\
+    /// COLORFGBG is set by some terminals to ```<fg>;<bg>``` using ANSI color numbers, returns
+    /// None if it is not set or not understood.
+    pub fn detect() -> Option<Background> {
+        let colorfgbg = env::var("COLORFGBG").ok()?;
+        let bg = colorfgbg.rsplit(';').next()?.parse::<u8>().ok()?;
+        match bg {
+            // black, the dark colors and dark grey
+            0..=6 | 8 => Some(Background::Dark),
+            // light grey and the bright colors
+            7 | 9..=15 => Some(Background::Light),
+            _ => None,
+        }
+    }
+
+    /// The default level colors for the background
+    pub(crate) fn level_colors(&self) -> [Color; 5] {
+        match self {
+            Background::Light => LIGHT_LEVEL_COLORS,
+            Background::Dark => DEFAULT_LEVEL_COLORS,
+        }
+    }
+}
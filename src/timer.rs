@@ -0,0 +1,36 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// A periodic background task.\
+/// Restarting or stopping the timer makes the running thread exit after its current interval.
+pub(crate) struct Timer {
+    generation: Arc<AtomicUsize>,
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer {
+            generation: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Run action every interval, replacing any action started before
+    pub fn start<F: Fn() + Send + 'static>(&self, interval: Duration, action: F) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let current = self.generation.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if current.load(Ordering::SeqCst) != generation {
+                break;
+            }
+            action();
+        });
+    }
+
+    /// Stop the running action
+    pub fn stop(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
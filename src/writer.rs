@@ -0,0 +1,30 @@
+use super::logger_params::LoggerParams;
+use std::io::{Result, Write};
+use std::sync::{Arc, Mutex};
+
+/// A handle writing raw output to the log destination, see Logger::writer_handle.\
+/// Output is written unformatted while the logger is locked, so it does not interleave with log
+/// lines. Flushing the handle flushes the logger.
+pub struct LogWriter {
+    inner: Arc<Mutex<LoggerParams>>,
+}
+
+impl LogWriter {
+    pub(crate) fn new(inner: Arc<Mutex<LoggerParams>>) -> LogWriter {
+        LogWriter { inner }
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut guarded_params = self.inner.lock().unwrap();
+        guarded_params.write_unformatted(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let mut guarded_params = self.inner.lock().unwrap();
+        guarded_params.flush();
+        Ok(())
+    }
+}